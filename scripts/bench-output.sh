@@ -0,0 +1,44 @@
+#!/usr/bin/env bash
+# Time generation through the stdout, file and null outputs on a fixed synthetic workload.
+#
+# Usage: scripts/bench-output.sh [WORDS] [BASES]   (default: 2000000 words x 5 bases)
+#
+# Builds the release binary of the current checkout unless MKSUB points at another one,
+# so two revisions can be compared with e.g.
+#   git worktree add /tmp/old <rev> && (cd /tmp/old && cargo build --release)
+#   MKSUB=/tmp/old/target/release/mksub-rs scripts/bench-output.sh
+set -euo pipefail
+
+words=${1:-2000000}
+bases=${2:-5}
+runs=${RUNS:-2}
+
+if [ -z "${MKSUB:-}" ]; then
+    cargo build --release --quiet
+    MKSUB=target/release/mksub-rs
+fi
+
+tmp=$(mktemp -d)
+trap 'rm -rf "$tmp"' EXIT
+awk -v n="$words" 'BEGIN { for (i = 1; i <= n; i++) print "w" i }' > "$tmp/words.txt"
+awk -v n="$bases" 'BEGIN { for (i = 1; i <= n; i++) print "example" i ".com" }' > "$tmp/bases.txt"
+args=(--domain-file "$tmp/bases.txt" -w "$tmp/words.txt" --no-color)
+
+TIMEFORMAT='%R s'
+bench() {
+    local label=$1
+    shift
+    for _ in $(seq "$runs"); do
+        printf '%-8s ' "$label"
+        { time "$@" 2>/dev/null; } 2>&1
+        rm -f "$tmp"/out*
+    done
+}
+
+echo "$MKSUB: $words words x $bases bases"
+bench stdout sh -c '"$0" "$@" | cat > "'"$tmp"'/out.txt"' "$MKSUB" "${args[@]}"
+bench file "$MKSUB" "${args[@]}" -o "$tmp/out.txt"
+# --sink only exists from the Sink backends onwards
+if "$MKSUB" --help | grep -q -- '--sink'; then
+    bench null "$MKSUB" "${args[@]}" --sink null
+fi
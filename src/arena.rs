@@ -0,0 +1,163 @@
+use crate::bloom::hash64;
+use std::collections::hash_map::{Entry, HashMap};
+use std::ops::Index;
+
+/// Words stored back to back in one buffer and addressed by index. Avoids a heap allocation and
+/// a 24-byte `String` header per word, which dominates memory for multi-million-entry lists
+#[derive(Debug, Default)]
+pub struct WordArena {
+    text: String,
+    /// End offset of each word in `text`; word `i` starts where word `i - 1` ends
+    ends: Vec<u32>,
+}
+
+impl WordArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `word`, returning its index. `None` once the arena would exceed 4 GiB of text
+    pub fn push(&mut self, word: &str) -> Option<usize> {
+        let end = u32::try_from(self.text.len() + word.len()).ok()?;
+        self.text.push_str(word);
+        self.ends.push(end);
+        Some(self.ends.len() - 1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &str> + '_ {
+        (0..self.len()).map(move |i| &self[i])
+    }
+
+    /// Release spare capacity left over from loading
+    pub fn shrink_to_fit(&mut self) {
+        self.text.shrink_to_fit();
+        self.ends.shrink_to_fit();
+    }
+
+    /// Heap memory held by the arena
+    pub fn size_bytes(&self) -> usize {
+        self.text.capacity() + self.ends.capacity() * std::mem::size_of::<u32>()
+    }
+
+    /// Estimated heap memory of the same words held as a `Vec<String>` plus a `HashSet<String>`
+    /// copy for deduplication, the layout this arena replaces
+    pub fn owned_size_bytes(&self) -> usize {
+        let header = std::mem::size_of::<String>();
+        // Vec slot and set slot (plus control byte) per word, and two copies of the text
+        self.len() * (2 * header + 1) + 2 * self.text.len()
+    }
+}
+
+impl Index<usize> for WordArena {
+    type Output = str;
+
+    fn index(&self, i: usize) -> &str {
+        let start = if i == 0 { 0 } else { self.ends[i - 1] as usize };
+        &self.text[start..self.ends[i] as usize]
+    }
+}
+
+impl<S: AsRef<str>> FromIterator<S> for WordArena {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        let mut arena = WordArena::new();
+        for word in iter {
+            arena.push(word.as_ref()).expect("word arena exceeds 4 GiB");
+        }
+        arena
+    }
+}
+
+/// Interns words into a `WordArena`, deduplicating by 64-bit hash instead of keeping a second
+/// copy of every word. Hash collisions between different words fall back to an exact map
+#[derive(Default)]
+pub struct WordSet {
+    arena: WordArena,
+    by_hash: HashMap<u64, u32>,
+    collisions: HashMap<Box<str>, u32>,
+}
+
+impl WordSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index of `word` if already present
+    pub fn get(&self, word: &str) -> Option<usize> {
+        let &i = self.by_hash.get(&hash64(3, word))?;
+        if &self.arena[i as usize] == word {
+            Some(i as usize)
+        } else {
+            self.collisions.get(word).map(|&i| i as usize)
+        }
+    }
+
+    /// Add `word` if new, returning its index and whether it was inserted.
+    /// `None` once the arena is full
+    pub fn insert(&mut self, word: &str) -> Option<(usize, bool)> {
+        if let Some(i) = self.get(word) {
+            return Some((i, false));
+        }
+
+        let i = self.arena.push(word)?;
+        match self.by_hash.entry(hash64(3, word)) {
+            Entry::Occupied(_) => {
+                self.collisions.insert(word.into(), i as u32);
+            }
+            Entry::Vacant(slot) => {
+                slot.insert(i as u32);
+            }
+        }
+        Some((i, true))
+    }
+
+    /// Drop the dedupe index, keeping only the words
+    pub fn into_arena(self) -> WordArena {
+        let mut arena = self.arena;
+        arena.shrink_to_fit();
+        arena
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arena_indexing() {
+        let arena: WordArena = ["api", "", "xn--mnchen-3ya", "dev"].into_iter().collect();
+        assert_eq!(arena.len(), 4);
+        assert_eq!(&arena[0], "api");
+        assert_eq!(&arena[1], "");
+        assert_eq!(&arena[2], "xn--mnchen-3ya");
+        assert_eq!(&arena[3], "dev");
+        assert_eq!(arena.iter().collect::<Vec<_>>(), vec!["api", "", "xn--mnchen-3ya", "dev"]);
+    }
+
+    #[test]
+    fn test_word_set_dedupe() {
+        let mut set = WordSet::new();
+        assert_eq!(set.insert("api"), Some((0, true)));
+        assert_eq!(set.insert("dev"), Some((1, true)));
+        assert_eq!(set.insert("api"), Some((0, false)));
+        assert_eq!(set.get("dev"), Some(1));
+        assert_eq!(set.get("www"), None);
+
+        let arena = set.into_arena();
+        assert_eq!(arena.iter().collect::<Vec<_>>(), vec!["api", "dev"]);
+    }
+
+    #[test]
+    fn test_smaller_than_owned_strings() {
+        let mut arena: WordArena = (0..10_000).map(|i| format!("word{}", i)).collect();
+        arena.shrink_to_fit();
+        assert!(arena.size_bytes() * 4 < arena.owned_size_bytes());
+    }
+}
@@ -0,0 +1,123 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Deterministic 64-bit hash of a name, distinct per `seed`
+pub fn hash64(seed: u64, name: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    name.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Fixed-size Bloom filter over names. Bits are atomic so one filter can be shared by all
+/// generator threads without locking
+pub struct BloomFilter {
+    bits: Vec<AtomicU64>,
+    num_bits: u64,
+    num_hashes: u32,
+}
+
+impl BloomFilter {
+    /// Size the filter for `capacity` names at the given false-positive rate
+    pub fn new(capacity: usize, fp_rate: f64) -> Self {
+        let (num_bits, num_hashes) = dimensions(capacity, fp_rate);
+        let words = num_bits.div_ceil(64) as usize;
+
+        Self {
+            bits: (0..words).map(|_| AtomicU64::new(0)).collect(),
+            num_bits,
+            num_hashes,
+        }
+    }
+
+    /// Bit positions for `name` via double hashing
+    fn positions(&self, name: &str) -> impl Iterator<Item = u64> + use<> {
+        let h1 = hash64(0, name);
+        let h2 = hash64(1, name) | 1;
+        let num_bits = self.num_bits;
+        (0..self.num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+    }
+
+    /// Add `name`. Returns true if it was possibly present already
+    pub fn insert(&self, name: &str) -> bool {
+        let mut present = true;
+        for pos in self.positions(name) {
+            let mask = 1u64 << (pos % 64);
+            let prev = self.bits[(pos / 64) as usize].fetch_or(mask, Ordering::Relaxed);
+            present &= prev & mask != 0;
+        }
+        present
+    }
+
+    /// Whether `name` is possibly present. Never false for inserted names
+    pub fn contains(&self, name: &str) -> bool {
+        self.positions(name).all(|pos| {
+            let mask = 1u64 << (pos % 64);
+            self.bits[(pos / 64) as usize].load(Ordering::Relaxed) & mask != 0
+        })
+    }
+
+    /// Memory used by the bit array
+    pub fn size_bytes(&self) -> usize {
+        self.bits.len() * 8
+    }
+
+    /// Memory a filter for `capacity` names would need, without allocating it
+    pub fn size_for(capacity: usize, fp_rate: f64) -> u64 {
+        dimensions(capacity, fp_rate).0.div_ceil(64) * 8
+    }
+}
+
+/// Bit count and hash count for `capacity` names at `fp_rate`
+fn dimensions(capacity: usize, fp_rate: f64) -> (u64, u32) {
+    let capacity = capacity.max(1) as f64;
+    let fp_rate = fp_rate.clamp(1e-12, 0.5);
+    let ln2 = std::f64::consts::LN_2;
+
+    let num_bits = (-(capacity * fp_rate.ln()) / (ln2 * ln2)).ceil().max(64.0) as u64;
+    let num_hashes = ((num_bits as f64 / capacity) * ln2).round().clamp(1.0, 32.0) as u32;
+    (num_bits, num_hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_false_negatives() {
+        let bloom = BloomFilter::new(1000, 0.01);
+        for i in 0..1000 {
+            bloom.insert(&format!("host{}.example.com", i));
+        }
+        for i in 0..1000 {
+            assert!(bloom.contains(&format!("host{}.example.com", i)));
+        }
+    }
+
+    #[test]
+    fn test_size_for() {
+        assert_eq!(BloomFilter::size_for(10_000, 0.01) as usize, BloomFilter::new(10_000, 0.01).size_bytes());
+        // An unbounded combination count must not be allocated blindly
+        assert!(BloomFilter::size_for(usize::MAX, 0.0001) > 1 << 40);
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        let bloom = BloomFilter::new(10_000, 0.01);
+        for i in 0..10_000 {
+            bloom.insert(&format!("in{}.example.com", i));
+        }
+        let false_positives = (0..10_000)
+            .filter(|i| bloom.contains(&format!("out{}.example.com", i)))
+            .count();
+        // Expected ~100; allow generous slack
+        assert!(false_positives < 300, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn test_insert_reports_presence() {
+        let bloom = BloomFilter::new(100, 0.001);
+        assert!(!bloom.insert("api.example.com"));
+        assert!(bloom.insert("api.example.com"));
+    }
+}
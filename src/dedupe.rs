@@ -0,0 +1,112 @@
+use crate::bloom::{hash64, BloomFilter};
+use clap::ValueEnum;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Number of independently locked shards in exact mode
+const EXACT_SHARDS: usize = 64;
+
+/// How emitted names are remembered for deduplication
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DedupeMode {
+    /// Keep every emitted name. No false positives, memory grows with output
+    Exact,
+    /// Fixed-size Bloom filter. Bounded memory, but a false positive drops a new name
+    Bloom,
+}
+
+enum Seen {
+    Exact(Vec<Mutex<HashSet<Box<str>>>>),
+    Bloom(BloomFilter),
+}
+
+/// Drops names already emitted earlier in the run, across bases and generation modes
+pub struct Dedupe {
+    seen: Seen,
+    duplicates: AtomicU64,
+}
+
+impl Dedupe {
+    /// `capacity` sizes the Bloom filter and is ignored in exact mode
+    pub fn new(mode: DedupeMode, capacity: usize, fp_rate: f64) -> Self {
+        let seen = match mode {
+            DedupeMode::Exact => Seen::Exact((0..EXACT_SHARDS).map(|_| Mutex::default()).collect()),
+            DedupeMode::Bloom => Seen::Bloom(BloomFilter::new(capacity, fp_rate)),
+        };
+        Self { seen, duplicates: AtomicU64::new(0) }
+    }
+
+    /// Record `name`, returning true the first time it is seen
+    pub fn first_seen(&self, name: &str) -> bool {
+        let first = match &self.seen {
+            Seen::Exact(shards) => {
+                let shard = hash64(2, name) as usize % shards.len();
+                let mut set = shards[shard].lock().unwrap_or_else(|e| e.into_inner());
+                if set.contains(name) {
+                    false
+                } else {
+                    set.insert(name.into());
+                    true
+                }
+            }
+            Seen::Bloom(bloom) => !bloom.insert(name),
+        };
+        if !first {
+            self.duplicates.fetch_add(1, Ordering::Relaxed);
+        }
+        first
+    }
+
+    /// Names dropped as repeats
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates.load(Ordering::Relaxed)
+    }
+
+    /// Memory reserved up front (Bloom mode only)
+    pub fn reserved_bytes(&self) -> usize {
+        match &self.seen {
+            Seen::Exact(_) => 0,
+            Seen::Bloom(bloom) => bloom.size_bytes(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exact_dedupe() {
+        let dedupe = Dedupe::new(DedupeMode::Exact, 0, 0.0);
+        assert!(dedupe.first_seen("api.example.com"));
+        assert!(dedupe.first_seen("api.dev.example.com"));
+        assert!(!dedupe.first_seen("api.example.com"));
+        assert_eq!(dedupe.duplicates(), 1);
+    }
+
+    #[test]
+    fn test_bloom_dedupe() {
+        let dedupe = Dedupe::new(DedupeMode::Bloom, 1000, 0.0001);
+        for i in 0..100 {
+            assert!(dedupe.first_seen(&format!("host{}.example.com", i)));
+        }
+        for i in 0..100 {
+            assert!(!dedupe.first_seen(&format!("host{}.example.com", i)));
+        }
+        assert_eq!(dedupe.duplicates(), 100);
+    }
+
+    #[test]
+    fn test_concurrent_exact_dedupe() {
+        use rayon::prelude::*;
+
+        let dedupe = Dedupe::new(DedupeMode::Exact, 0, 0.0);
+        let firsts = (0..4000)
+            .into_par_iter()
+            .filter(|i| dedupe.first_seen(&format!("host{}.example.com", i % 1000)))
+            .count();
+        assert_eq!(firsts, 1000);
+        assert_eq!(dedupe.duplicates(), 3000);
+    }
+}
@@ -0,0 +1,182 @@
+use clap::ValueEnum;
+
+/// Maximum length of a single DNS label (RFC 1035)
+pub const MAX_LABEL_LEN: usize = 63;
+
+/// Maximum length of a full hostname without the trailing dot (RFC 1035)
+pub const MAX_NAME_LEN: usize = 253;
+
+/// What to do with wordlist entries that are not valid hostname labels
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum InvalidAction {
+    /// Skip the entry
+    Drop,
+    /// Replace invalid characters with `-`, trim edge hyphens and truncate to 63 characters
+    Sanitize,
+}
+
+/// Label validation policy applied to wordlist entries at load time
+#[derive(Clone, Copy, Debug)]
+pub struct LabelPolicy {
+    pub action: InvalidAction,
+    /// Accept `_` in labels, as used by SRV-style names like `_sip._tcp`
+    pub allow_underscore: bool,
+}
+
+impl Default for LabelPolicy {
+    fn default() -> Self {
+        Self { action: InvalidAction::Drop, allow_underscore: false }
+    }
+}
+
+/// Outcome of checking a word against a `LabelPolicy`
+#[derive(Debug, PartialEq, Eq)]
+pub enum Checked {
+    Valid,
+    Sanitized(String),
+    Rejected,
+}
+
+impl LabelPolicy {
+    /// Check a normalized word, which may span several dot-separated labels
+    pub fn check(&self, word: &str) -> Checked {
+        if word.split('.').all(|label| is_valid_label(label, self.allow_underscore)) {
+            return Checked::Valid;
+        }
+
+        if self.action == InvalidAction::Drop {
+            return Checked::Rejected;
+        }
+
+        let labels: Vec<String> = word
+            .split('.')
+            .map(|label| sanitize_label(label, self.allow_underscore))
+            .filter(|label| !label.is_empty())
+            .collect();
+
+        if labels.is_empty() {
+            Checked::Rejected
+        } else {
+            Checked::Sanitized(labels.join("."))
+        }
+    }
+}
+
+fn is_label_char(c: char, allow_underscore: bool) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || (allow_underscore && c == '_')
+}
+
+/// RFC 1123 label check: 1-63 letters, digits and inner hyphens
+pub fn is_valid_label(label: &str, allow_underscore: bool) -> bool {
+    !label.is_empty()
+        && label.len() <= MAX_LABEL_LEN
+        && !label.starts_with('-')
+        && !label.ends_with('-')
+        && label.chars().all(|c| is_label_char(c, allow_underscore))
+}
+
+/// Collapse runs of invalid characters into `-`, trim edge hyphens and truncate
+fn sanitize_label(label: &str, allow_underscore: bool) -> String {
+    let mut out = String::with_capacity(label.len());
+    for c in label.chars() {
+        if is_label_char(c, allow_underscore) {
+            out.push(c);
+        } else if !out.ends_with('-') {
+            out.push('-');
+        }
+    }
+
+    let mut out = out.trim_matches('-').to_string();
+    if out.len() > MAX_LABEL_LEN {
+        out.truncate(MAX_LABEL_LEN);
+        out.truncate(out.trim_end_matches('-').len());
+    }
+    out
+}
+
+/// Convert a name with Unicode labels to its `xn--` ASCII form using UTS-46 processing.
+/// ASCII input is returned unchanged; `None` when the name cannot be encoded
+pub fn to_ascii(name: &str) -> Option<String> {
+    if name.is_ascii() {
+        return Some(name.to_string());
+    }
+    idna::domain_to_ascii(name).ok().filter(|ascii| !ascii.is_empty())
+}
+
+/// Convert `xn--` labels back to Unicode for display. Names that fail to decode are kept as-is
+pub fn to_unicode(name: &str) -> String {
+    if !name.contains("xn--") {
+        return name.to_string();
+    }
+    match idna::domain_to_unicode(name) {
+        (unicode, Ok(())) => unicode,
+        (_, Err(_)) => name.to_string(),
+    }
+}
+
+/// Whether a full hostname fits the 253-byte limit
+pub fn is_valid_name_len(name: &str) -> bool {
+    name.len() <= MAX_NAME_LEN
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_label() {
+        assert!(is_valid_label("api", false));
+        assert!(is_valid_label("api-v2", false));
+        assert!(is_valid_label(&"a".repeat(63), false));
+        assert!(!is_valid_label(&"a".repeat(64), false));
+        assert!(!is_valid_label("-api", false));
+        assert!(!is_valid_label("api-", false));
+        assert!(!is_valid_label("a b", false));
+        assert!(!is_valid_label("*", false));
+        assert!(!is_valid_label("", false));
+        assert!(!is_valid_label("_sip", false));
+        assert!(is_valid_label("_sip", true));
+    }
+
+    #[test]
+    fn test_policy_drop() {
+        let policy = LabelPolicy::default();
+        assert_eq!(policy.check("api"), Checked::Valid);
+        assert_eq!(policy.check("api.v2"), Checked::Valid);
+        assert_eq!(policy.check("my api"), Checked::Rejected);
+        assert_eq!(policy.check("_tcp"), Checked::Rejected);
+    }
+
+    #[test]
+    fn test_policy_sanitize() {
+        let policy = LabelPolicy { action: InvalidAction::Sanitize, allow_underscore: false };
+        assert_eq!(policy.check("my  api"), Checked::Sanitized("my-api".to_string()));
+        assert_eq!(policy.check("*.api"), Checked::Sanitized("api".to_string()));
+        assert_eq!(policy.check("_sip"), Checked::Sanitized("sip".to_string()));
+        assert_eq!(policy.check("**"), Checked::Rejected);
+
+        let long = format!("{}-x", "a".repeat(62));
+        assert_eq!(policy.check(&format!("{}!", long)), Checked::Sanitized("a".repeat(62)));
+    }
+
+    #[test]
+    fn test_policy_underscore() {
+        let policy = LabelPolicy { action: InvalidAction::Drop, allow_underscore: true };
+        assert_eq!(policy.check("_sip._tcp"), Checked::Valid);
+    }
+
+    #[test]
+    fn test_idna_roundtrip() {
+        assert_eq!(to_ascii("api").as_deref(), Some("api"));
+        assert_eq!(to_ascii("bücher").as_deref(), Some("xn--bcher-kva"));
+        assert_eq!(to_ascii("Bücher.example").as_deref(), Some("xn--bcher-kva.example"));
+        assert_eq!(to_unicode("xn--bcher-kva.example.com"), "bücher.example.com");
+        assert_eq!(to_unicode("api.example.com"), "api.example.com");
+    }
+
+    #[test]
+    fn test_name_len() {
+        assert!(is_valid_name_len(&"a".repeat(253)));
+        assert!(!is_valid_name_len(&"a".repeat(254)));
+    }
+}
@@ -0,0 +1,125 @@
+use crate::bloom::{hash64, BloomFilter};
+use crate::{dns, io_utils};
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+/// Already-known hostnames that should not be emitted again
+pub enum KnownNames {
+    /// 64-bit hashes of every name. Collisions are possible but negligible in practice
+    Hashes(HashSet<u64>),
+    /// Probabilistic membership at a fixed false-positive rate, for very large files
+    Bloom(BloomFilter),
+}
+
+impl KnownNames {
+    /// Load names from a file of hostnames or URLs, one per line. With `bloom_fp_rate`, the file
+    /// is read twice: once to size the filter and once to fill it
+    pub fn load(path: &str, bloom_fp_rate: Option<f64>) -> Result<(Self, usize)> {
+        let mut count = 0usize;
+
+        let known = match bloom_fp_rate {
+            None => {
+                let mut hashes = HashSet::new();
+                for_each_name(path, |name| {
+                    hashes.insert(hash64(0, name));
+                    count += 1;
+                })?;
+                hashes.shrink_to_fit();
+                KnownNames::Hashes(hashes)
+            }
+            Some(fp_rate) => {
+                let mut capacity = 0usize;
+                for_each_name(path, |_| capacity += 1)?;
+
+                let bloom = BloomFilter::new(capacity, fp_rate);
+                for_each_name(path, |name| {
+                    bloom.insert(name);
+                    count += 1;
+                })?;
+                KnownNames::Bloom(bloom)
+            }
+        };
+
+        Ok((known, count))
+    }
+
+    /// Whether `name` is (possibly, in Bloom mode) already known
+    pub fn contains(&self, name: &str) -> bool {
+        match self {
+            KnownNames::Hashes(hashes) => hashes.contains(&hash64(0, name)),
+            KnownNames::Bloom(bloom) => bloom.contains(name),
+        }
+    }
+
+    /// Approximate memory held by the set
+    pub fn size_bytes(&self) -> usize {
+        match self {
+            // Hash table slot plus control byte per entry
+            KnownNames::Hashes(hashes) => hashes.capacity() * (std::mem::size_of::<u64>() + 1),
+            KnownNames::Bloom(bloom) => bloom.size_bytes(),
+        }
+    }
+}
+
+/// Call `f` with every normalized name in `path`, in the same form the generator emits them
+fn for_each_name(path: &str, mut f: impl FnMut(&str)) -> Result<()> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open exclude file: {}", path))?;
+    let reader = BufReader::new(file);
+
+    for line in reader.lines() {
+        let line = line.context("Failed to read line from exclude file")?;
+        let Some(host) = io_utils::extract_host(&line) else {
+            continue;
+        };
+        if let Some(name) = dns::to_ascii(&host.to_lowercase()) {
+            f(&name);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn known_file() -> Result<NamedTempFile> {
+        let mut temp_file = NamedTempFile::new()?;
+        writeln!(temp_file, "api.example.com")?;
+        writeln!(temp_file, "WWW.Example.com.")?;
+        writeln!(temp_file, "https://cdn.example.com/x")?;
+        writeln!(temp_file)?;
+        Ok(temp_file)
+    }
+
+    #[test]
+    fn test_hashed_mode() -> Result<()> {
+        let file = known_file()?;
+        let (known, count) = KnownNames::load(file.path().to_str().unwrap(), None)?;
+
+        assert_eq!(count, 3);
+        assert!(known.contains("api.example.com"));
+        assert!(known.contains("www.example.com"));
+        assert!(known.contains("cdn.example.com"));
+        assert!(!known.contains("dev.example.com"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bloom_mode() -> Result<()> {
+        let file = known_file()?;
+        let (known, count) = KnownNames::load(file.path().to_str().unwrap(), Some(0.0001))?;
+
+        assert_eq!(count, 3);
+        assert!(matches!(known, KnownNames::Bloom(_)));
+        assert!(known.contains("api.example.com"));
+        assert!(known.contains("www.example.com"));
+        assert!(!known.contains("dev.example.com"));
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use crate::io_utils;
+use crate::psl::SuffixList;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::io::BufRead;
+
+/// Count word tokens in the subdomain part of hostnames or URLs, one per line.
+/// Hosts not under any of `bases` are skipped; without bases the registrable domain from
+/// `suffixes` is stripped. Returns tokens ordered by descending count and the number of hosts
+/// that contributed.
+pub fn extract_words<R: BufRead>(
+    reader: R,
+    bases: &[String],
+    suffixes: &SuffixList,
+    min_length: usize,
+) -> Result<(Vec<(String, u64)>, usize)> {
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    let mut used = 0usize;
+
+    for line in reader.lines() {
+        let line = line.context("Failed to read hostname")?;
+        let Some(host) = io_utils::extract_host(&line) else {
+            continue;
+        };
+
+        // Bases match case-insensitively; the prefix keeps its original case so camelCase survives
+        let Some(prefix) = io_utils::strip_base(host, bases, suffixes) else {
+            continue;
+        };
+
+        let mut contributed = false;
+        for token in tokenize(prefix) {
+            if token.chars().count() >= min_length {
+                *counts.entry(token).or_default() += 1;
+                contributed = true;
+            }
+        }
+        if contributed {
+            used += 1;
+        }
+    }
+
+    let mut ranked: Vec<(String, u64)> = counts.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    Ok((ranked, used))
+}
+
+/// Split a subdomain into lowercase tokens on dots, hyphens, digits and other non-letters,
+/// and on camelCase boundaries (`devApi` -> `dev`, `api`; `HTTPServer` -> `http`, `server`)
+fn tokenize(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphabetic() {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+
+        if c.is_uppercase() && !current.is_empty() {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev.is_lowercase() || (prev.is_uppercase() && next_lower) {
+                tokens.push(std::mem::take(&mut current));
+            }
+        }
+
+        current.extend(c.to_lowercase());
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(tokenize("api-prod.eu"), vec!["api", "prod", "eu"]);
+        assert_eq!(tokenize("web01.db2"), vec!["web", "db"]);
+        assert_eq!(tokenize("devApi"), vec!["dev", "api"]);
+        assert_eq!(tokenize("HTTPServer"), vec!["http", "server"]);
+        assert_eq!(tokenize("--1--"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_extract_words_ranked() -> Result<()> {
+        let input = "\
+api.example.com
+https://api-v2.example.com/login
+stagingApi.dev.example.com
+api.other.org
+example.com
+";
+        let bases = vec!["example.com".to_string()];
+        let (words, used) = extract_words(Cursor::new(input), &bases, &SuffixList::embedded()?, 1)?;
+
+        assert_eq!(used, 3);
+        assert_eq!(words[0], ("api".to_string(), 3));
+        assert!(words.contains(&("staging".to_string(), 1)));
+        assert!(words.contains(&("dev".to_string(), 1)));
+        assert!(words.contains(&("v".to_string(), 1)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_words_min_length() -> Result<()> {
+        let input = "a1.cdn.example.com\nx.mail.example.co.uk\n";
+        let (words, _) = extract_words(Cursor::new(input), &[], &SuffixList::embedded()?, 2)?;
+
+        assert_eq!(words, vec![("cdn".to_string(), 1), ("mail".to_string(), 1)]);
+        Ok(())
+    }
+}
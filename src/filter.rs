@@ -0,0 +1,181 @@
+use crate::arena::WordArena;
+use crate::generator::Levels;
+use crate::scope::glob_to_regex;
+use anyhow::{Context, Result};
+use regex::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Translate a filter expression into a regex. Expressions are regexes unless prefixed with
+/// `glob:`, where `*` matches any run of characters
+fn expression_to_regex(expr: &str) -> String {
+    match expr.strip_prefix("glob:") {
+        Some(glob) => glob_to_regex(glob),
+        None => expr.to_string(),
+    }
+}
+
+fn build_set(exprs: &[String], case_insensitive: bool) -> Result<Option<RegexSet>> {
+    if exprs.is_empty() {
+        return Ok(None);
+    }
+    let set = RegexSetBuilder::new(exprs.iter().map(|e| expression_to_regex(e)))
+        .case_insensitive(case_insensitive)
+        .build()
+        .with_context(|| format!("Failed to compile output filter: {}", exprs.join(", ")))?;
+    Ok(Some(set))
+}
+
+/// Keep/drop expressions evaluated on every emitted name
+pub struct NameFilter {
+    /// A name must match at least one of these, when present
+    keep: Option<RegexSet>,
+    /// A name matching any of these is dropped
+    drop: Option<RegexSet>,
+    dropped: AtomicU64,
+}
+
+impl NameFilter {
+    /// Build from `--match` and `--filter` expressions. `None` when neither is given
+    pub fn new(keep: &[String], drop: &[String], case_insensitive: bool) -> Result<Option<Self>> {
+        if keep.is_empty() && drop.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            keep: build_set(keep, case_insensitive)?,
+            drop: build_set(drop, case_insensitive)?,
+            dropped: AtomicU64::new(0),
+        }))
+    }
+
+    pub fn allows(&self, name: &str) -> bool {
+        let keep = self.keep.as_ref().is_none_or(|set| set.is_match(name))
+            && !self.drop.as_ref().is_some_and(|set| set.is_match(name));
+        if !keep {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        keep
+    }
+
+    /// Names rejected so far
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// A word expression bound to one depth, written `LEVEL=EXPR` on the command line
+#[derive(Clone, Debug)]
+pub struct LevelRule {
+    pub level: u32,
+    pub expr: String,
+}
+
+/// Clap value parser for `LEVEL=EXPR`
+pub fn parse_level_rule(value: &str) -> Result<LevelRule, String> {
+    let (level, expr) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected LEVEL=EXPR, got '{}'", value))?;
+    let level: u32 = level
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid level '{}'", level))?;
+    if level == 0 {
+        return Err("levels start at 1".to_string());
+    }
+    Ok(LevelRule { level, expr: expr.to_string() })
+}
+
+/// Apply per-level keep (`matches`) and drop (`filters`) word rules to `levels`
+pub fn restrict_levels(
+    levels: &mut Levels,
+    words: &WordArena,
+    matches: &[LevelRule],
+    filters: &[LevelRule],
+    case_insensitive: bool,
+) -> Result<()> {
+    let compile = |rule: &LevelRule| -> Result<Regex> {
+        RegexBuilder::new(&expression_to_regex(&rule.expr))
+            .case_insensitive(case_insensitive)
+            .build()
+            .with_context(|| format!("Failed to compile level {} word filter: {}", rule.level, rule.expr))
+    };
+
+    let mut rule_levels: Vec<u32> = matches.iter().chain(filters).map(|r| r.level).collect();
+    rule_levels.sort_unstable();
+    rule_levels.dedup();
+
+    for level in rule_levels {
+        let keep: Vec<Regex> = matches.iter().filter(|r| r.level == level).map(compile).collect::<Result<_>>()?;
+        let drop: Vec<Regex> = filters.iter().filter(|r| r.level == level).map(compile).collect::<Result<_>>()?;
+
+        let mask = words
+            .iter()
+            .map(|word| {
+                (keep.is_empty() || keep.iter().any(|re| re.is_match(word)))
+                    && !drop.iter().any(|re| re.is_match(word))
+            })
+            .collect();
+        levels.restrict(level, mask);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_filter() -> Result<()> {
+        let filter = NameFilter::new(
+            &[r"^api\.".to_string(), "glob:*.cdn.*".to_string()],
+            &["test".to_string()],
+            true,
+        )?
+        .unwrap();
+
+        assert!(filter.allows("api.example.com"));
+        assert!(filter.allows("img.cdn.example.com"));
+        assert!(!filter.allows("www.example.com"));
+        assert!(!filter.allows("api.test.example.com"));
+        assert_eq!(filter.dropped(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_name_filter_empty() -> Result<()> {
+        assert!(NameFilter::new(&[], &[], true)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_level_rule() {
+        let rule = parse_level_rule("2=^test").unwrap();
+        assert_eq!(rule.level, 2);
+        assert_eq!(rule.expr, "^test");
+        assert!(parse_level_rule("test").is_err());
+        assert!(parse_level_rule("0=x").is_err());
+        assert!(parse_level_rule("x=y").is_err());
+    }
+
+    #[test]
+    fn test_restrict_levels() -> Result<()> {
+        let words: WordArena = ["api", "test", "dev"].into_iter().collect();
+        let mut levels = Levels::new(1, 3);
+        restrict_levels(
+            &mut levels,
+            &words,
+            &[parse_level_rule("1=^(api|test)$").unwrap()],
+            &[parse_level_rule("1=test").unwrap(), parse_level_rule("2=test").unwrap()],
+            true,
+        )?;
+
+        assert!(levels.allows(1, 0));
+        assert!(!levels.allows(1, 1));
+        assert!(!levels.allows(1, 2));
+        assert!(levels.allows(2, 0));
+        assert!(!levels.allows(2, 1));
+        assert!(levels.allows(2, 2));
+        assert!(levels.allows(3, 1));
+        Ok(())
+    }
+}
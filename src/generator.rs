@@ -1,81 +1,302 @@
+use crate::arena::WordArena;
 use rayon::prelude::*;
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::BinaryHeap;
 use std::sync::atomic::{AtomicBool, Ordering};
 
-static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+/// Depth bounds and per-depth word restrictions for generation
+pub struct Levels {
+    pub min: u32,
+    pub max: u32,
+    /// `allowed[level - 1][word]`; levels without a mask allow every word
+    allowed: Vec<Option<Vec<bool>>>,
+}
+
+impl Levels {
+    pub fn new(min: u32, max: u32) -> Self {
+        Self { min, max, allowed: Vec::new() }
+    }
+
+    /// Restrict `level` to the words whose `mask` entry is true
+    pub fn restrict(&mut self, level: u32, mask: Vec<bool>) {
+        let idx = level as usize - 1;
+        if self.allowed.len() <= idx {
+            self.allowed.resize(idx + 1, None);
+        }
+        self.allowed[idx] = Some(mask);
+    }
+
+    /// Whether word index `word` may appear at `level` (1 = directly left of the base)
+    pub fn allows(&self, level: u32, word: usize) -> bool {
+        match self.allowed.get(level as usize - 1) {
+            Some(Some(mask)) => mask[word],
+            _ => true,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.max == 0 || self.min > self.max
+    }
+
+    /// Number of names exhaustive generation emits per base for a wordlist of `words` entries
+    pub fn combinations(&self, words: usize) -> u128 {
+        if self.is_empty() {
+            return 0;
+        }
+
+        let mut total = 0u128;
+        let mut chains = 1u128;
+        for level in 1..=self.max {
+            let allowed = match self.allowed.get(level as usize - 1) {
+                Some(Some(mask)) => mask.iter().filter(|&&ok| ok).count(),
+                _ => words,
+            };
+            chains = chains.saturating_mul(allowed as u128);
+            if level >= self.min {
+                total = total.saturating_add(chains);
+            }
+        }
+        total
+    }
+}
 
-/// Generate all subdomain combinations for a base domain up to specified level
+/// Generate all subdomain combinations for a base domain with depths in [levels.min..levels.max].
+/// Levels below `levels.min` are still traversed to reach deeper names, but never emitted.
+/// `emit` receives each name with its depth. Setting `cancel` stops generation promptly.
 pub fn generate_subdomains<F>(
     base_domain: &str,
-    words: &[String],
-    max_level: u32,
+    words: &WordArena,
+    levels: &Levels,
     threads: usize,
+    cancel: &AtomicBool,
     emit: F,
 )
 where
-    F: Fn(String) + Sync + Send,
+    F: Fn(String, u32) + Sync + Send,
 {
-    if max_level == 0 || words.is_empty() {
+    if levels.is_empty() || words.is_empty() {
         return;
     }
 
     // Parallelize over first word (w1) using rayon
-    words
-        .par_iter()
-        .with_max_len(if threads > 0 { 
-            std::cmp::max(1, words.len() / threads) 
-        } else { 
-            1 
-        })
-        .for_each(|w1| {
-            if SHUTDOWN.load(Ordering::Relaxed) {
+    (0..words.len())
+        .into_par_iter()
+        .with_max_len(words.len().checked_div(threads).map_or(1, |n| n.max(1)))
+        .for_each(|i| {
+            if cancel.load(Ordering::Relaxed) || !levels.allows(1, i) {
                 return;
             }
 
             // Start with level 1: w1.base
-            generate_combinations(base_domain, words, vec![w1], 1, max_level, &emit);
+            generate_combinations(base_domain, words, vec![&words[i]], 1, levels, cancel, &emit);
         });
 }
 
 /// Recursively generate combinations for all levels
 fn generate_combinations<F>(
     base_domain: &str,
-    words: &[String], 
-    current_chain: Vec<&String>,
+    words: &WordArena,
+    current_chain: Vec<&str>,
     current_level: u32,
-    max_level: u32,
+    levels: &Levels,
+    cancel: &AtomicBool,
     emit: &F,
 )
 where
-    F: Fn(String) + Sync + Send,
+    F: Fn(String, u32) + Sync + Send,
 {
-    if current_level > max_level || SHUTDOWN.load(Ordering::Relaxed) {
+    if current_level > levels.max || cancel.load(Ordering::Relaxed) {
         return;
     }
     
     // Emit current combination: chain[n-1].chain[n-2]...chain[0].base
-    let subdomain = format!(
-        "{}.{}",
-        current_chain
-            .iter()
-            .map(|s| s.as_str())
-            .collect::<Vec<_>>()
-            .join("."),
-        base_domain
-    );
-    emit(subdomain);
+    if current_level >= levels.min {
+        let subdomain = format!(
+            "{}.{}",
+            current_chain.join("."),
+            base_domain
+        );
+        emit(subdomain, current_level);
+    }
     
     // Generate next level if not at max
-    if current_level < max_level {
-        for word in words {
-            if SHUTDOWN.load(Ordering::Relaxed) {
+    if current_level < levels.max {
+        for (i, word) in words.iter().enumerate() {
+            if cancel.load(Ordering::Relaxed) {
                 return;
             }
+            if !levels.allows(current_level + 1, i) {
+                continue;
+            }
             
             let mut next_chain = Vec::with_capacity(current_chain.len() + 1);
             next_chain.push(word);
             next_chain.extend_from_slice(&current_chain);
             
-            generate_combinations(base_domain, words, next_chain, current_level + 1, max_level, emit);
+            generate_combinations(base_domain, words, next_chain, current_level + 1, levels, cancel, emit);
+        }
+    }
+}
+
+/// Orders candidate words for best-first generation
+pub trait Ranking {
+    /// The `k`-th most likely word index to appear directly left of `prev` (`None` at the base),
+    /// with its log probability. Successive `k` must not increase in probability
+    fn successor(&self, prev: Option<u32>, k: usize) -> Option<(u32, f64)>;
+}
+
+/// Context-free ranking by word weight: each word's probability is its weight over the total
+pub struct WeightRanking {
+    order: Vec<u32>,
+    log_probs: Vec<f64>,
+}
+
+impl WeightRanking {
+    /// Build from weights parallel to the wordlist; an empty slice ranks all `len` words equally.
+    /// Zero weights count as 1 so every word keeps a finite log probability
+    pub fn new(weights: &[u64], len: usize) -> Self {
+        let weight = |i: usize| weights.get(i).copied().unwrap_or(1).max(1) as f64;
+
+        let mut order: Vec<u32> = (0..len as u32).collect();
+        order.sort_by(|&a, &b| weight(b as usize).total_cmp(&weight(a as usize)));
+
+        let total: f64 = (0..len).map(weight).sum();
+        let log_probs = order
+            .iter()
+            .map(|&i| (weight(i as usize) / total).ln())
+            .collect();
+
+        Self { order, log_probs }
+    }
+
+    /// Word indices from most to least likely
+    pub fn order(&self) -> &[u32] {
+        &self.order
+    }
+
+    /// Log probability of the word at `rank` in `order()`
+    pub fn log_prob(&self, rank: usize) -> f64 {
+        self.log_probs[rank]
+    }
+}
+
+impl Ranking for WeightRanking {
+    fn successor(&self, _prev: Option<u32>, k: usize) -> Option<(u32, f64)> {
+        self.order.get(k).map(|&word| (word, self.log_probs[k]))
+    }
+}
+
+/// Candidate name in best-first generation. `chain[0]` is the word nearest the base;
+/// `rank` and `last` describe how the newest word was chosen so its sibling can be derived
+struct Candidate {
+    score: f64,
+    chain: Vec<u32>,
+    rank: usize,
+    last: f64,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        // Higher score first; ties go to shorter chains, then lower word indices
+        self.score
+            .total_cmp(&other.score)
+            .then_with(|| other.chain.len().cmp(&self.chain.len()))
+            .then_with(|| other.chain.cmp(&self.chain))
+    }
+}
+
+/// Generate combinations in descending joint probability as scored by `ranking`, within
+/// `levels`. `emit` returns whether it kept the name; generation stops after `top` kept names
+/// when given, or once `cancel` is set.
+///
+/// Runs sequentially: candidates are expanded lazily from a heap, so each popped name pushes at
+/// most two successors (next-ranked word at the same position, and one level deeper).
+pub fn generate_ranked<R, F>(
+    base_domain: &str,
+    words: &WordArena,
+    ranking: &R,
+    levels: &Levels,
+    top: Option<usize>,
+    cancel: &AtomicBool,
+    emit: F,
+)
+where
+    R: Ranking + ?Sized,
+    F: Fn(String, u32) -> bool,
+{
+    if levels.is_empty() || words.is_empty() || top == Some(0) {
+        return;
+    }
+
+    let mut heap = BinaryHeap::new();
+    if let Some((word, lp)) = ranking.successor(None, 0) {
+        heap.push(Candidate { score: lp, chain: vec![word], rank: 0, last: lp });
+    }
+    let mut emitted = 0usize;
+
+    while let Some(candidate) = heap.pop() {
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let level = candidate.chain.len() as u32;
+        let newest = *candidate.chain.last().unwrap();
+        let allowed = levels.allows(level, newest as usize);
+        if allowed && level >= levels.min {
+            let subdomain = format!(
+                "{}.{}",
+                candidate
+                    .chain
+                    .iter()
+                    .rev()
+                    .map(|&word| &words[word as usize])
+                    .collect::<Vec<_>>()
+                    .join("."),
+                base_domain
+            );
+            if emit(subdomain, level) {
+                emitted += 1;
+            }
+            if top.is_some_and(|top| emitted >= top) {
+                return;
+            }
+        }
+
+        // Next-ranked word at the newest position, in the same context
+        let prev = candidate.chain.len().checked_sub(2).map(|i| candidate.chain[i]);
+        if let Some((word, lp)) = ranking.successor(prev, candidate.rank + 1) {
+            let mut chain = candidate.chain.clone();
+            *chain.last_mut().unwrap() = word;
+            heap.push(Candidate {
+                score: candidate.score - candidate.last + lp,
+                chain,
+                rank: candidate.rank + 1,
+                last: lp,
+            });
+        }
+
+        // Best word one level deeper. Chains through a disallowed word are pruned
+        if allowed
+            && level < levels.max
+            && let Some((word, lp)) = ranking.successor(Some(newest), 0)
+        {
+            let mut chain = candidate.chain;
+            chain.push(word);
+            heap.push(Candidate { score: candidate.score + lp, chain, rank: 0, last: lp });
         }
     }
 }
@@ -87,14 +308,14 @@ mod tests {
 
     #[test]
     fn test_generate_level_1() {
-        let words = vec!["api".to_string(), "cdn".to_string()];
+        let words: WordArena = ["api", "cdn"].into_iter().collect();
         let results = Mutex::new(Vec::new());
         
-        let emit = |line: String| {
+        let emit = |line: String, _level: u32| {
             results.lock().unwrap().push(line);
         };
 
-        generate_subdomains("example.com", &words, 1, 10, emit);
+        generate_subdomains("example.com", &words, &Levels::new(1, 1), 10, &AtomicBool::new(false), emit);
         
         let mut results = results.into_inner().unwrap();
         results.sort();
@@ -107,14 +328,14 @@ mod tests {
 
     #[test]
     fn test_generate_level_2() {
-        let words = vec!["x".to_string(), "y".to_string()];
+        let words: WordArena = ["x", "y"].into_iter().collect();
         let results = Mutex::new(Vec::new());
         
-        let emit = |line: String| {
+        let emit = |line: String, _level: u32| {
             results.lock().unwrap().push(line);
         };
 
-        generate_subdomains("example.com", &words, 2, 10, emit);
+        generate_subdomains("example.com", &words, &Levels::new(1, 2), 10, &AtomicBool::new(false), emit);
         
         let mut results = results.into_inner().unwrap();
         results.sort();
@@ -134,14 +355,14 @@ mod tests {
 
     #[test]
     fn test_generate_level_3() {
-        let words = vec!["a".to_string(), "b".to_string()];
+        let words: WordArena = ["a", "b"].into_iter().collect();
         let results = Mutex::new(Vec::new());
         
-        let emit = |line: String| {
+        let emit = |line: String, _level: u32| {
             results.lock().unwrap().push(line);
         };
 
-        generate_subdomains("test.com", &words, 3, 10, emit);
+        generate_subdomains("test.com", &words, &Levels::new(1, 3), 10, &AtomicBool::new(false), emit);
         
         let results = results.into_inner().unwrap();
         
@@ -168,16 +389,43 @@ mod tests {
         assert_eq!(level_3_count, 8); // a.a.a.test.com, etc.
     }
 
+    #[test]
+    fn test_cancel_stops_generation() {
+        let words: WordArena = ["a", "b", "c", "d"].into_iter().collect();
+        let cancel = AtomicBool::new(false);
+        let results = Mutex::new(Vec::new());
+
+        let emit = |line: String, _level: u32| {
+            let mut results = results.lock().unwrap();
+            results.push(line);
+            if results.len() == 5 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            true
+        };
+
+        generate_subdomains("test.com", &words, &Levels::new(1, 4), 1, &cancel, |line, level| {
+            emit(line, level);
+        });
+        assert!(results.lock().unwrap().len() < 50);
+
+        cancel.store(false, Ordering::Relaxed);
+        let ranking = WeightRanking::new(&[], words.len());
+        results.lock().unwrap().clear();
+        generate_ranked("test.com", &words, &ranking, &Levels::new(1, 4), None, &cancel, emit);
+        assert_eq!(results.lock().unwrap().len(), 5);
+    }
+
     #[test]
     fn test_empty_words() {
-        let words: Vec<String> = vec![];
+        let words = WordArena::new();
         let results = Mutex::new(Vec::new());
         
-        let emit = |line: String| {
+        let emit = |line: String, _level: u32| {
             results.lock().unwrap().push(line);
         };
 
-        generate_subdomains("example.com", &words, 2, 10, emit);
+        generate_subdomains("example.com", &words, &Levels::new(1, 2), 10, &AtomicBool::new(false), emit);
         
         let results = results.into_inner().unwrap();
         assert_eq!(results.len(), 0);
@@ -185,16 +433,209 @@ mod tests {
 
     #[test]
     fn test_level_0() {
-        let words = vec!["api".to_string()];
+        let words: WordArena = ["api"].into_iter().collect();
+        let results = Mutex::new(Vec::new());
+        
+        let emit = |line: String, _level: u32| {
+            results.lock().unwrap().push(line);
+        };
+
+        generate_subdomains("example.com", &words, &Levels::new(1, 0), 10, &AtomicBool::new(false), emit);
+        
+        let results = results.into_inner().unwrap();
+        assert_eq!(results.len(), 0);
+    }
+
+    #[test]
+    fn test_only_level() {
+        let words: WordArena = ["a", "b"].into_iter().collect();
+        let results = Mutex::new(Vec::new());
+        
+        let emit = |line: String, _level: u32| {
+            results.lock().unwrap().push(line);
+        };
+
+        generate_subdomains("test.com", &words, &Levels::new(2, 2), 10, &AtomicBool::new(false), emit);
+        
+        let mut results = results.into_inner().unwrap();
+        results.sort();
+        
+        assert_eq!(results, vec![
+            "a.a.test.com",
+            "a.b.test.com",
+            "b.a.test.com",
+            "b.b.test.com",
+        ]);
+    }
+
+    #[test]
+    fn test_min_level_range() {
+        let words: WordArena = ["a", "b"].into_iter().collect();
         let results = Mutex::new(Vec::new());
         
-        let emit = |line: String| {
+        let emit = |line: String, _level: u32| {
             results.lock().unwrap().push(line);
         };
 
-        generate_subdomains("example.com", &words, 0, 10, emit);
+        generate_subdomains("test.com", &words, &Levels::new(2, 3), 10, &AtomicBool::new(false), emit);
+        
+        let results = results.into_inner().unwrap();
+        
+        // 2^2 + 2^3 = 12, no level 1 names
+        assert_eq!(results.len(), 12);
+        assert!(results.iter().all(|s| s.split('.').count() >= 4));
+    }
+
+    #[test]
+    fn test_min_level_above_max() {
+        let words: WordArena = ["a"].into_iter().collect();
+        let results = Mutex::new(Vec::new());
+        
+        let emit = |line: String, _level: u32| {
+            results.lock().unwrap().push(line);
+        };
+
+        generate_subdomains("test.com", &words, &Levels::new(3, 2), 10, &AtomicBool::new(false), emit);
         
         let results = results.into_inner().unwrap();
         assert_eq!(results.len(), 0);
     }
+
+    #[test]
+    fn test_ranked_order() {
+        let words: WordArena = ["dev", "api", "www"].into_iter().collect();
+        let weights = vec![2, 6, 2];
+        let results = Mutex::new(Vec::new());
+        
+        let emit = |line: String, _level: u32| {
+            results.lock().unwrap().push(line);
+            true
+        };
+
+        let ranking = WeightRanking::new(&weights, words.len());
+        generate_ranked("example.com", &words, &ranking, &Levels::new(1, 2), Some(4), &AtomicBool::new(false), emit);
+        
+        let results = results.into_inner().unwrap();
+        
+        // p(api)=0.6 > p(api.api)=0.36 > p(dev)=p(www)=0.2
+        assert_eq!(results, vec![
+            "api.example.com",
+            "api.api.example.com",
+            "dev.example.com",
+            "www.example.com",
+        ]);
+    }
+
+    #[test]
+    fn test_ranked_top_counts_kept_names() {
+        let words: WordArena = ["dev", "api", "www"].into_iter().collect();
+        let ranking = WeightRanking::new(&[2, 6, 2], words.len());
+        let results = Mutex::new(Vec::new());
+        // Drop every name containing "api", like an output filter would
+        let emit = |line: String, _level: u32| {
+            let keep = !line.contains("api");
+            if keep {
+                results.lock().unwrap().push(line);
+            }
+            keep
+        };
+
+        generate_ranked("example.com", &words, &ranking, &Levels::new(1, 2), Some(3), &AtomicBool::new(false), emit);
+        assert_eq!(results.into_inner().unwrap(), vec!["dev.example.com", "www.example.com", "dev.dev.example.com"]);
+    }
+
+    #[test]
+    fn test_zero_weights() {
+        let ranking = WeightRanking::new(&[0, 3, 0], 3);
+        assert!((0..3).all(|rank| ranking.log_prob(rank).is_finite()));
+        assert_eq!(ranking.order()[0], 1);
+
+        let words: WordArena = ["a", "b"].into_iter().collect();
+        let ranking = WeightRanking::new(&[0, 0], words.len());
+        let results = Mutex::new(Vec::new());
+        let emit = |line: String, _level: u32| {
+            results.lock().unwrap().push(line);
+            true
+        };
+        generate_ranked("test.com", &words, &ranking, &Levels::new(1, 2), None, &AtomicBool::new(false), emit);
+        assert_eq!(results.into_inner().unwrap().len(), 2 + 4);
+    }
+
+    #[test]
+    fn test_ranked_exhaustive() {
+        let words: WordArena = ["a", "b", "c"].into_iter().collect();
+        let weights = vec![5, 3, 1];
+        let results = Mutex::new(Vec::new());
+        
+        let emit = |line: String, _level: u32| {
+            results.lock().unwrap().push(line);
+            true
+        };
+
+        let ranking = WeightRanking::new(&weights, words.len());
+        generate_ranked("test.com", &words, &ranking, &Levels::new(2, 3), None, &AtomicBool::new(false), emit);
+        
+        let results = results.into_inner().unwrap();
+        
+        // Every level 2 and 3 combination exactly once
+        assert_eq!(results.len(), 9 + 27);
+        let unique: std::collections::HashSet<_> = results.iter().collect();
+        assert_eq!(unique.len(), results.len());
+        assert_eq!(results[0], "a.a.test.com");
+        assert_eq!(results.last().unwrap(), "c.c.c.test.com");
+    }
+
+    #[test]
+    fn test_level_restrictions() {
+        let words: WordArena = ["api", "test"].into_iter().collect();
+        let mut levels = Levels::new(1, 2);
+        levels.restrict(2, vec![true, false]);
+        let results = Mutex::new(Vec::new());
+        
+        let emit = |line: String, _level: u32| {
+            results.lock().unwrap().push(line);
+        };
+
+        generate_subdomains("example.com", &words, &levels, 10, &AtomicBool::new(false), emit);
+        
+        let mut results = results.into_inner().unwrap();
+        results.sort();
+        
+        assert_eq!(results, vec![
+            "api.api.example.com",
+            "api.example.com",
+            "api.test.example.com",
+            "test.example.com",
+        ]);
+    }
+
+    #[test]
+    fn test_ranked_level_restrictions() {
+        let words: WordArena = ["api", "test"].into_iter().collect();
+        let mut levels = Levels::new(2, 2);
+        levels.restrict(1, vec![false, true]);
+        let results = Mutex::new(Vec::new());
+        
+        let emit = |line: String, _level: u32| {
+            results.lock().unwrap().push(line);
+            true
+        };
+
+        let ranking = WeightRanking::new(&[5, 1], words.len());
+        generate_ranked("example.com", &words, &ranking, &levels, None, &AtomicBool::new(false), emit);
+        
+        let results = results.into_inner().unwrap();
+        assert_eq!(results, vec!["api.test.example.com", "test.test.example.com"]);
+    }
+
+    #[test]
+    fn test_combinations() {
+        assert_eq!(Levels::new(1, 3).combinations(2), 2 + 4 + 8);
+        assert_eq!(Levels::new(2, 3).combinations(10), 100 + 1000);
+        assert_eq!(Levels::new(3, 2).combinations(10), 0);
+
+        let mut levels = Levels::new(1, 2);
+        levels.restrict(2, vec![true, false, false]);
+        assert_eq!(levels.combinations(3), 3 + 3);
+    }
 }
@@ -1,21 +1,83 @@
+use crate::arena::{WordArena, WordSet};
+use crate::dns::{self, Checked, LabelPolicy};
+use crate::psl::{BaseResolver, SuffixList};
 use anyhow::{Context, Result};
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
 use std::fs::File;
 use std::io::{self, BufRead, BufReader, IsTerminal};
 
-/// Read base domains from various sources
+/// Tallies of base domain input lines that did not become new bases
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BaseCounts {
+    /// Non-empty lines without a usable hostname
+    pub skipped: usize,
+    /// Lines that normalized to an already seen base
+    pub duplicates: usize,
+    /// Lines naming a bare public suffix such as `co.uk`
+    pub public_suffixes: usize,
+}
+
+/// Turns raw input lines into deduplicated bases
+struct BaseParser<'a> {
+    resolver: Option<&'a BaseResolver>,
+    seen: HashSet<String>,
+    counts: BaseCounts,
+}
+
+impl<'a> BaseParser<'a> {
+    fn new(resolver: Option<&'a BaseResolver>) -> Self {
+        Self { resolver, seen: HashSet::new(), counts: BaseCounts::default() }
+    }
+
+    /// Append the new bases found on `line` to `out`
+    fn parse_line(&mut self, line: &str, out: &mut impl Extend<String>) {
+        if line.trim().is_empty() {
+            return;
+        }
+        let Some(host) = parse_base(line) else {
+            self.counts.skipped += 1;
+            return;
+        };
+
+        let bases = match self.resolver {
+            Some(resolver) => resolver.resolve(&host),
+            None => vec![host],
+        };
+        if bases.is_empty() {
+            self.counts.public_suffixes += 1;
+        }
+
+        for base in bases {
+            if self.seen.insert(base.clone()) {
+                out.extend(Some(base));
+            } else {
+                self.counts.duplicates += 1;
+            }
+        }
+    }
+}
+
+/// Base domains collected from all input sources
+#[derive(Default)]
+pub struct Domains {
+    pub bases: Vec<String>,
+    pub counts: BaseCounts,
+}
+
+/// Read base domains from various sources. Lines may be hostnames, URLs or wildcard entries;
+/// see `parse_base`. With a resolver, hosts are expanded to registrable domains and bare public
+/// suffixes are refused. Stdin is read only when neither a domain nor a file is given
 pub fn read_domains(
     single_domain: Option<&str>,
     domain_file: Option<&str>,
-) -> Result<Vec<String>> {
-    let mut domains = Vec::new();
+    resolver: Option<&BaseResolver>,
+) -> Result<Domains> {
+    let mut bases = Vec::new();
+    let mut parser = BaseParser::new(resolver);
 
     // Handle single domain
     if let Some(domain) = single_domain {
-        let trimmed = domain.trim();
-        if !trimmed.is_empty() {
-            domains.push(trimmed.to_string());
-        }
+        parser.parse_line(domain, &mut bases);
     }
 
     // Handle domain file
@@ -26,15 +88,12 @@ pub fn read_domains(
         
         for line in reader.lines() {
             let line = line.context("Failed to read line from domain file")?;
-            let trimmed = line.trim();
-            if !trimmed.is_empty() {
-                domains.push(trimmed.to_string());
-            }
+            parser.parse_line(&line, &mut bases);
         }
     }
 
     // Handle stdin if no other sources
-    if domains.is_empty() {
+    if single_domain.is_none() && domain_file.is_none() {
         if io::stdin().is_terminal() {
             anyhow::bail!("No domains provided and stdin is a TTY");
         }
@@ -44,22 +103,107 @@ pub fn read_domains(
         
         for line in reader.lines() {
             let line = line.context("Failed to read line from stdin")?;
-            let trimmed = line.trim();
-            if !trimmed.is_empty() {
-                domains.push(trimmed.to_string());
+            parser.parse_line(&line, &mut bases);
+        }
+    }
+
+    Ok(Domains { bases, counts: parser.counts })
+}
+
+/// Bases parsed from a reader as lines arrive, so generation can start before EOF.
+/// Parsing and deduplication match `read_domains`
+pub struct DomainStream<'a, R: BufRead> {
+    lines: io::Lines<R>,
+    parser: BaseParser<'a>,
+    pending: VecDeque<String>,
+}
+
+impl<'a, R: BufRead> DomainStream<'a, R> {
+    pub fn new(reader: R, resolver: Option<&'a BaseResolver>) -> Self {
+        Self { lines: reader.lines(), parser: BaseParser::new(resolver), pending: VecDeque::new() }
+    }
+
+    /// Tallies of the lines consumed so far
+    pub fn counts(&self) -> BaseCounts {
+        self.parser.counts
+    }
+}
+
+impl<R: BufRead> Iterator for DomainStream<'_, R> {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(base) = self.pending.pop_front() {
+                return Some(Ok(base));
+            }
+            match self.lines.next()? {
+                Ok(line) => self.parser.parse_line(&line, &mut self.pending),
+                Err(e) => return Some(Err(e).context("Failed to read line from stdin")),
             }
         }
     }
+}
+
+/// Parse a base domain from a noisy input line: extract the host from URLs, strip wildcard
+/// prefixes, ports and trailing dots, lowercase, and encode Unicode labels to their xn-- form.
+/// `None` when no valid hostname remains (including IPv4 addresses)
+pub fn parse_base(line: &str) -> Option<String> {
+    let host = extract_host(line)?.to_lowercase();
+    let host = dns::to_ascii(&host)?;
+
+    let valid = dns::is_valid_name_len(&host)
+        && host.split('.').all(|label| dns::is_valid_label(label, true))
+        && !host.rsplit('.').next()?.bytes().all(|b| b.is_ascii_digit());
 
-    Ok(domains)
+    valid.then_some(host)
 }
 
-/// Read wordlist, apply normalization, deduplication, and optional regex filtering
+/// Deduplicated wordlist with optional per-word weights
+pub struct Wordlist {
+    pub words: WordArena,
+    /// Weights parallel to `words` (e.g. hit counts). Empty when no line carried a weight
+    pub weights: Vec<u64>,
+    /// Entries dropped for not being valid DNS labels
+    pub rejected: usize,
+    /// Entries rewritten into valid DNS labels
+    pub sanitized: usize,
+    /// Non-empty lines read from the file
+    pub lines: usize,
+    /// Valid entries left out by the regex filter
+    pub filtered: usize,
+    /// Entries merged into an earlier identical word
+    pub duplicates: usize,
+}
+
+impl Wordlist {
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn is_weighted(&self) -> bool {
+        !self.weights.is_empty()
+    }
+
+    /// Heap memory held by the words and weights
+    pub fn size_bytes(&self) -> usize {
+        self.words.size_bytes() + self.weights.capacity() * std::mem::size_of::<u64>()
+    }
+}
+
+/// Read wordlist, apply normalization, DNS label validation, deduplication, and optional regex
+/// filtering. Lines may carry a trailing weight (`api 98123`); duplicate words have their weights summed
+/// and unweighted lines count as weight 1.
 pub fn read_wordlist(
     path: &str,
     regex_filter: Option<&str>,
     case_insensitive: bool,
-) -> Result<Vec<String>> {
+    policy: LabelPolicy,
+) -> Result<Wordlist> {
     let file = File::open(path)
         .with_context(|| format!("Failed to open wordlist file: {}", path))?;
     let reader = BufReader::new(file);
@@ -77,29 +221,151 @@ pub fn read_wordlist(
         None
     };
 
-    let mut word_set = HashSet::new();
-    let mut words = Vec::new();
+    let mut words = WordSet::new();
+    let mut weights: Vec<u64> = Vec::new();
+    let mut weighted = false;
+    let mut rejected = 0usize;
+    let mut sanitized = 0usize;
+    let mut lines = 0usize;
+    let mut filtered = 0usize;
+    let mut duplicates = 0usize;
 
     for line in reader.lines() {
         let line = line.context("Failed to read line from wordlist")?;
-        let normalized = normalize_word(&line);
+        let (word, weight) = split_weight(&line);
+        let mut normalized = normalize_word(word);
         
         if normalized.is_empty() {
             continue;
         }
+        lines += 1;
+
+        // Unicode entries are encoded to their xn-- form before validation
+        match dns::to_ascii(&normalized) {
+            Some(ascii) => normalized = ascii,
+            None => {
+                rejected += 1;
+                continue;
+            }
+        }
+
+        match policy.check(&normalized) {
+            Checked::Valid => {}
+            Checked::Sanitized(fixed) => {
+                sanitized += 1;
+                normalized = fixed;
+            }
+            Checked::Rejected => {
+                rejected += 1;
+                continue;
+            }
+        }
 
         // Apply regex filter if provided
         if regex.as_ref().is_some_and(|re| !re.is_match(&normalized)) {
+            filtered += 1;
             continue;
         }
 
-        // Deduplicate using HashSet
-        if word_set.insert(normalized.clone()) {
-            words.push(normalized);
+        weighted |= weight.is_some();
+        let weight = weight.unwrap_or(1);
+
+        // Deduplicate, accumulating weights of repeated entries
+        let (idx, inserted) = words
+            .insert(&normalized)
+            .with_context(|| format!("Wordlist too large (over 4 GiB of words): {}", path))?;
+        if inserted {
+            weights.push(weight);
+        } else {
+            duplicates += 1;
+            weights[idx] = weights[idx].saturating_add(weight);
         }
     }
 
-    Ok(words)
+    if weighted {
+        weights.shrink_to_fit();
+    } else {
+        weights = Vec::new();
+    }
+
+    Ok(Wordlist {
+        words: words.into_arena(),
+        weights,
+        rejected,
+        sanitized,
+        lines,
+        filtered,
+        duplicates,
+    })
+}
+
+/// Split an optional trailing weight off a wordlist line (`word 123` or `word\t123`)
+fn split_weight(line: &str) -> (&str, Option<u64>) {
+    let trimmed = line.trim();
+    if let Some((word, weight)) = trimmed.rsplit_once(char::is_whitespace)
+        && let Ok(weight) = weight.parse::<u64>()
+    {
+        return (word, Some(weight));
+    }
+    (trimmed, None)
+}
+
+/// Extract the hostname from a hostname or URL line, dropping scheme, credentials, path, port,
+/// wildcard prefix and trailing dot. Case is preserved. Only the first whitespace-separated
+/// field is considered, so resolver output like `host A 1.2.3.4` works as well
+pub fn extract_host(line: &str) -> Option<&str> {
+    let mut host = line.split_whitespace().next()?;
+
+    if let Some(i) = host.find("://") {
+        host = &host[i + 3..];
+    }
+    if let Some(i) = host.find(['/', '?', '#']) {
+        host = &host[..i];
+    }
+    if let Some(i) = host.rfind('@') {
+        host = &host[i + 1..];
+    }
+    if host.starts_with('[') {
+        // IPv6 literal, not a hostname
+        return None;
+    }
+    if let Some((name, port)) = host.rsplit_once(':')
+        && port.bytes().all(|b| b.is_ascii_digit())
+    {
+        host = name;
+    }
+
+    while let Some(rest) = host.strip_prefix("*.") {
+        host = rest;
+    }
+    let host = host.trim_matches('.');
+
+    if host.is_empty() || host.contains(':') {
+        None
+    } else {
+        Some(host)
+    }
+}
+
+/// Part of `host` left of its base domain, without the joining dot, in the host's original
+/// form. The host is compared in its lowercase IDNA (`xn--`) form, like the bases; without
+/// `bases` its registrable domain (eTLD+1) is the base
+pub fn strip_base<'a>(host: &'a str, bases: &[String], suffixes: &SuffixList) -> Option<&'a str> {
+    let ascii = dns::to_ascii(host)?.to_ascii_lowercase();
+    let base = if bases.is_empty() {
+        suffixes.registrable(&ascii)?
+    } else {
+        bases
+            .iter()
+            .map(String::as_str)
+            .filter(|base| ascii.strip_suffix(base).is_some_and(|rest| rest.ends_with('.')))
+            .max_by_key(|base| base.len())?
+    };
+
+    // IDNA encodes label by label, so the base spans as many labels of the original host
+    let keep = host.split('.').count().checked_sub(base.split('.').count())?;
+    let end = host.match_indices('.').nth(keep.checked_sub(1)?)?.0;
+    Some(&host[..end])
 }
 
 /// Normalize a word: lowercase, trim leading/trailing dots and whitespace
@@ -113,6 +379,7 @@ fn normalize_word(word: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::dns::InvalidAction;
     use std::io::Write;
     use tempfile::NamedTempFile;
 
@@ -136,7 +403,11 @@ mod tests {
         writeln!(temp_file)?; // empty
         writeln!(temp_file, "...")?; // only dots
         
-        let words = read_wordlist(temp_file.path().to_str().unwrap(), None, true)?;
+        let words = read_wordlist(temp_file.path().to_str().unwrap(), None, true, LabelPolicy::default())?;
+        assert!(!words.is_weighted());
+        assert_eq!(words.lines, 4);
+        assert_eq!(words.duplicates, 1);
+        let words: Vec<String> = words.words.iter().map(String::from).collect();
         
         assert_eq!(words.len(), 3);
         assert!(words.contains(&"api".to_string()));
@@ -157,8 +428,11 @@ mod tests {
         let words = read_wordlist(
             temp_file.path().to_str().unwrap(), 
             Some("^(api|img)$"), 
-            true
+            true,
+            LabelPolicy::default(),
         )?;
+        assert_eq!(words.filtered, 2);
+        let words: Vec<String> = words.words.iter().map(String::from).collect();
         
         assert_eq!(words.len(), 2);
         assert!(words.contains(&"api".to_string()));
@@ -169,13 +443,112 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_read_wordlist_weighted() -> Result<()> {
+        let mut temp_file = NamedTempFile::new()?;
+        writeln!(temp_file, "api 98123")?;
+        writeln!(temp_file, "cdn\t42")?;
+        writeln!(temp_file, "img")?; // no weight, counts as 1
+        writeln!(temp_file, "API 7")?; // duplicate, weights are summed
+        
+        let words = read_wordlist(temp_file.path().to_str().unwrap(), None, true, LabelPolicy::default())?;
+        
+        assert!(words.is_weighted());
+        assert_eq!(words.words.iter().collect::<Vec<_>>(), vec!["api", "cdn", "img"]);
+        assert_eq!(words.weights, vec![98130, 42, 1]);
+        
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_wordlist_label_policy() -> Result<()> {
+        let mut temp_file = NamedTempFile::new()?;
+        writeln!(temp_file, "api")?;
+        writeln!(temp_file, "my api")?;
+        writeln!(temp_file, "_sip")?;
+        writeln!(temp_file, "*")?;
+        writeln!(temp_file, "{}", "a".repeat(64))?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let words = read_wordlist(path, None, true, LabelPolicy::default())?;
+        assert_eq!(words.words.iter().collect::<Vec<_>>(), vec!["api"]);
+        assert_eq!(words.rejected, 4);
+
+        let policy = LabelPolicy { action: InvalidAction::Sanitize, allow_underscore: true };
+        let words = read_wordlist(path, None, true, policy)?;
+        assert_eq!(words.words.iter().collect::<Vec<_>>(), vec!["api", "my-api", "_sip", &"a".repeat(63)]);
+        assert_eq!(words.sanitized, 2);
+        assert_eq!(words.rejected, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_weight() {
+        assert_eq!(split_weight("api 12"), ("api", Some(12)));
+        assert_eq!(split_weight("  api\t12  "), ("api", Some(12)));
+        assert_eq!(split_weight("api"), ("api", None));
+        assert_eq!(split_weight("api x"), ("api x", None));
+    }
+
+    #[test]
+    fn test_strip_base() {
+        let suffixes = SuffixList::embedded().unwrap();
+        let bases = vec!["example.com".to_string(), "dev.example.com".to_string()];
+        assert_eq!(strip_base("api.prod.example.com", &bases, &suffixes), Some("api.prod"));
+        assert_eq!(strip_base("api.dev.example.com", &bases, &suffixes), Some("api"));
+        assert_eq!(strip_base("Api.Example.COM", &bases, &suffixes), Some("Api"));
+        assert_eq!(strip_base("example.com", &bases, &suffixes), None);
+        assert_eq!(strip_base("api.other.org", &bases, &suffixes), None);
+
+        // Unicode hosts match their IDNA-encoded base; the prefix keeps its original form
+        let bases = vec!["xn--mnchen-3ya.de".to_string()];
+        assert_eq!(strip_base("bücher.münchen.de", &bases, &suffixes), Some("bücher"));
+
+        // Without bases, the registrable domain is stripped
+        assert_eq!(strip_base("api.prod.example.com", &[], &suffixes), Some("api.prod"));
+        assert_eq!(strip_base("a.b.example.co.uk", &[], &suffixes), Some("a.b"));
+        assert_eq!(strip_base("example.com", &[], &suffixes), None);
+        assert_eq!(strip_base("co.uk", &[], &suffixes), None);
+    }
+
+    #[test]
+    fn test_extract_host() {
+        assert_eq!(extract_host("api.example.com"), Some("api.example.com"));
+        assert_eq!(extract_host("https://App.Example.com/login?x=1"), Some("App.Example.com"));
+        assert_eq!(extract_host("http://user:pw@example.com:8080/"), Some("example.com"));
+        assert_eq!(extract_host("*.example.com"), Some("example.com"));
+        assert_eq!(extract_host("example.com:443"), Some("example.com"));
+        assert_eq!(extract_host("example.com. A 1.2.3.4"), Some("example.com"));
+        assert_eq!(extract_host("http://[::1]:80/"), None);
+        assert_eq!(extract_host("   "), None);
+    }
+
     #[test]
     fn test_read_domains_single() -> Result<()> {
-        let domains = read_domains(Some("  example.com  "), None)?;
+        let domains = read_domains(Some("  example.com  "), None, None)?.bases;
         assert_eq!(domains, vec!["example.com"]);
         Ok(())
     }
 
+    #[test]
+    fn test_read_domains_idn() -> Result<()> {
+        let domains = read_domains(Some("bücher.example"), None, None)?.bases;
+        assert_eq!(domains, vec!["xn--bcher-kva.example"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_wordlist_idn() -> Result<()> {
+        let mut temp_file = NamedTempFile::new()?;
+        writeln!(temp_file, "Bücher")?;
+        writeln!(temp_file, "xn--bcher-kva")?; // same word, already encoded
+
+        let words = read_wordlist(temp_file.path().to_str().unwrap(), None, true, LabelPolicy::default())?;
+        assert_eq!(words.words.iter().collect::<Vec<_>>(), vec!["xn--bcher-kva"]);
+        Ok(())
+    }
+
     #[test]
     fn test_read_domains_file() -> Result<()> {
         let mut temp_file = NamedTempFile::new()?;
@@ -184,7 +557,7 @@ mod tests {
         writeln!(temp_file)?; // empty line
         writeln!(temp_file, "domain.net")?;
         
-        let domains = read_domains(None, Some(temp_file.path().to_str().unwrap()))?;
+        let domains = read_domains(None, Some(temp_file.path().to_str().unwrap()), None)?.bases;
         
         assert_eq!(domains.len(), 3);
         assert!(domains.contains(&"example.com".to_string()));
@@ -193,4 +566,69 @@ mod tests {
         
         Ok(())
     }
+
+    #[test]
+    fn test_parse_base() {
+        assert_eq!(parse_base("https://app.example.com/login").as_deref(), Some("app.example.com"));
+        assert_eq!(parse_base("*.example.com").as_deref(), Some("example.com"));
+        assert_eq!(parse_base("example.com:443").as_deref(), Some("example.com"));
+        assert_eq!(parse_base("Example.COM.").as_deref(), Some("example.com"));
+        assert_eq!(parse_base("_dmarc.example.com").as_deref(), Some("_dmarc.example.com"));
+        assert_eq!(parse_base("10.0.0.1"), None);
+        assert_eq!(parse_base("example.com A 1.2.3.4").as_deref(), Some("example.com"));
+        assert_eq!(parse_base("bad!host.com"), None);
+        assert_eq!(parse_base("-bad.com"), None);
+    }
+
+    #[test]
+    fn test_read_domains_noisy_file() -> Result<()> {
+        let mut temp_file = NamedTempFile::new()?;
+        writeln!(temp_file, "https://app.example.com/login")?;
+        writeln!(temp_file, "*.example.com")?;
+        writeln!(temp_file, "example.com:443")?;
+        writeln!(temp_file, "Example.COM.")?; // duplicate after normalization
+        writeln!(temp_file, "!!!")?;
+        writeln!(temp_file, "http://[::1]/")?;
+        writeln!(temp_file)?;
+
+        let domains = read_domains(None, Some(temp_file.path().to_str().unwrap()), None)?;
+
+        assert_eq!(domains.bases, vec!["app.example.com", "example.com"]);
+        assert_eq!(domains.counts.duplicates, 2);
+        assert_eq!(domains.counts.skipped, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_domains_psl() -> Result<()> {
+        use crate::psl::{BaseMode, SuffixList};
+
+        let mut temp_file = NamedTempFile::new()?;
+        writeln!(temp_file, "api.example.co.uk")?;
+        writeln!(temp_file, "www.example.co.uk")?;
+        writeln!(temp_file, "co.uk")?;
+
+        let resolver = BaseResolver::new(SuffixList::embedded()?, BaseMode::Both);
+        let domains = read_domains(None, Some(temp_file.path().to_str().unwrap()), Some(&resolver))?;
+
+        assert_eq!(domains.bases, vec!["api.example.co.uk", "example.co.uk", "www.example.co.uk"]);
+        assert_eq!(domains.counts.public_suffixes, 1);
+        assert_eq!(domains.counts.duplicates, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_domain_stream() -> Result<()> {
+        let input = std::io::Cursor::new("example.com\n\nhttps://example.com/x\n!!!\ntest.org\n");
+        let mut stream = DomainStream::new(input, None);
+
+        assert_eq!(stream.next().transpose()?.as_deref(), Some("example.com"));
+        assert_eq!(stream.next().transpose()?.as_deref(), Some("test.org"));
+        assert!(stream.next().is_none());
+
+        let counts = stream.counts();
+        assert_eq!(counts.duplicates, 1);
+        assert_eq!(counts.skipped, 1);
+        Ok(())
+    }
 }
@@ -0,0 +1,292 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Exit status when a line, byte or time cap cut the output short
+pub const EXIT_LIMIT_REACHED: i32 = 3;
+
+/// Clap value parser for byte sizes: `1048576`, `512K`, `100M`, `1G` (binary units, optional
+/// trailing `B` or `iB`)
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let lower = value.trim().to_ascii_lowercase();
+    let digits = lower.trim_end_matches("ib").trim_end_matches('b');
+    let (number, unit) = match digits.char_indices().find(|(_, c)| c.is_ascii_alphabetic()) {
+        Some((i, _)) => digits.split_at(i),
+        None => (digits, ""),
+    };
+    let shift = match unit {
+        "" => 0,
+        "k" => 10,
+        "m" => 20,
+        "g" => 30,
+        "t" => 40,
+        _ => return Err(format!("invalid size unit in '{}' (use K, M, G or T)", value)),
+    };
+    let number: u64 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", value))?;
+    number
+        .checked_mul(1u64 << shift)
+        .ok_or_else(|| format!("size '{}' is too large", value))
+}
+
+/// Clap value parser for durations: `90` or `90s`, `30m`, `2h`, `1h30m`
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+
+    let mut total = 0u64;
+    let mut number = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            _ => return Err(format!("invalid duration unit '{}' in '{}' (use s, m, h or d)", c, value)),
+        };
+        let n: u64 = number.parse().map_err(|_| format!("invalid duration '{}'", value))?;
+        total += n * unit;
+        number.clear();
+    }
+    if !number.is_empty() || total == 0 {
+        return Err(format!("invalid duration '{}'", value));
+    }
+    Ok(Duration::from_secs(total))
+}
+
+/// Line, byte and wall-time caps. Unset fields are unlimited
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Caps {
+    pub lines: Option<u64>,
+    pub bytes: Option<u64>,
+    pub duration: Option<Duration>,
+}
+
+impl Caps {
+    fn is_unlimited(&self) -> bool {
+        self.lines.is_none() && self.bytes.is_none() && self.duration.is_none()
+    }
+}
+
+/// Whether a name may still be written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Admit {
+    Yes,
+    /// The current base is out of budget; move on to the next one
+    BaseFull,
+    /// The run is out of budget; stop generating
+    RunFull,
+}
+
+/// Tracks output against run-wide and per-base caps. Shared by all generator threads
+pub struct Budget {
+    run: Caps,
+    base: Caps,
+    start: Instant,
+    lines: AtomicU64,
+    bytes: AtomicU64,
+    base_lines: AtomicU64,
+    base_bytes: AtomicU64,
+    /// When the current base started, in nanoseconds since `start`
+    base_start: AtomicU64,
+    run_full: AtomicBool,
+    base_full: AtomicBool,
+    truncated_bases: AtomicU64,
+}
+
+impl Budget {
+    /// `None` when neither set of caps limits anything
+    pub fn new(run: Caps, base: Caps) -> Option<Self> {
+        if run.is_unlimited() && base.is_unlimited() {
+            return None;
+        }
+        Some(Self {
+            run,
+            base,
+            start: Instant::now(),
+            lines: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            base_lines: AtomicU64::new(0),
+            base_bytes: AtomicU64::new(0),
+            base_start: AtomicU64::new(0),
+            run_full: AtomicBool::new(false),
+            base_full: AtomicBool::new(false),
+            truncated_bases: AtomicU64::new(0),
+        })
+    }
+
+    /// Reset the per-base caps before generating for the next base
+    pub fn start_base(&self) {
+        self.base_lines.store(0, Ordering::Relaxed);
+        self.base_bytes.store(0, Ordering::Relaxed);
+        self.base_start.store(self.start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        self.base_full.store(false, Ordering::Relaxed);
+    }
+
+    /// Check the wall-time caps without reserving anything. Called for every generated name,
+    /// so a run whose names are all filtered out still stops on time
+    pub fn check_time(&self) -> Admit {
+        if self.run_full.load(Ordering::Relaxed) {
+            return Admit::RunFull;
+        }
+        if self.run.duration.is_none() && self.base.duration.is_none() {
+            return Admit::Yes;
+        }
+
+        let elapsed = self.start.elapsed();
+        if self.run.duration.is_some_and(|max| elapsed >= max) {
+            return self.fill_run();
+        }
+        let base_elapsed = elapsed.saturating_sub(Duration::from_nanos(self.base_start.load(Ordering::Relaxed)));
+        if self.base.duration.is_some_and(|max| base_elapsed >= max) {
+            return self.fill_base();
+        }
+        Admit::Yes
+    }
+
+    /// Reserve room for one line of `bytes` bytes (newline included)
+    pub fn admit(&self, bytes: u64) -> Admit {
+        let time = self.check_time();
+        if time != Admit::Yes {
+            return time;
+        }
+
+        if !reserve(&self.lines, 1, self.run.lines) {
+            return self.fill_run();
+        }
+        if !reserve(&self.bytes, bytes, self.run.bytes) {
+            self.lines.fetch_sub(1, Ordering::Relaxed);
+            return self.fill_run();
+        }
+        if !reserve(&self.base_lines, 1, self.base.lines) {
+            self.lines.fetch_sub(1, Ordering::Relaxed);
+            self.bytes.fetch_sub(bytes, Ordering::Relaxed);
+            return self.fill_base();
+        }
+        if !reserve(&self.base_bytes, bytes, self.base.bytes) {
+            self.lines.fetch_sub(1, Ordering::Relaxed);
+            self.bytes.fetch_sub(bytes, Ordering::Relaxed);
+            self.base_lines.fetch_sub(1, Ordering::Relaxed);
+            return self.fill_base();
+        }
+        Admit::Yes
+    }
+
+    fn fill_run(&self) -> Admit {
+        self.run_full.store(true, Ordering::Relaxed);
+        Admit::RunFull
+    }
+
+    fn fill_base(&self) -> Admit {
+        if !self.base_full.swap(true, Ordering::Relaxed) {
+            self.truncated_bases.fetch_add(1, Ordering::Relaxed);
+        }
+        Admit::BaseFull
+    }
+
+    /// A run-wide cap stopped generation
+    pub fn run_full(&self) -> bool {
+        self.run_full.load(Ordering::Relaxed)
+    }
+
+    /// Bases cut short by a per-base cap
+    pub fn truncated_bases(&self) -> u64 {
+        self.truncated_bases.load(Ordering::Relaxed)
+    }
+
+    /// Any cap cut the output short
+    pub fn reached(&self) -> bool {
+        self.run_full() || self.truncated_bases() > 0
+    }
+}
+
+/// Add `amount` to `counter` unless that would exceed `max`
+fn reserve(counter: &AtomicU64, amount: u64, max: Option<u64>) -> bool {
+    let Some(max) = max else {
+        counter.fetch_add(amount, Ordering::Relaxed);
+        return true;
+    };
+    counter
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+            used.checked_add(amount).filter(|&total| total <= max)
+        })
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1000"), Ok(1000));
+        assert_eq!(parse_size("512K"), Ok(512 * 1024));
+        assert_eq!(parse_size("100MB"), Ok(100 << 20));
+        assert_eq!(parse_size("1GiB"), Ok(1 << 30));
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("G").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(1800)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert!(parse_duration("10x").is_err());
+        assert!(parse_duration("1h30").is_err());
+        assert!(parse_duration("0m").is_err());
+    }
+
+    #[test]
+    fn test_run_caps() {
+        let budget = Budget::new(Caps { lines: Some(3), bytes: Some(25), duration: None }, Caps::default()).unwrap();
+        assert_eq!(budget.admit(10), Admit::Yes);
+        assert_eq!(budget.admit(10), Admit::Yes);
+        // Would exceed the byte cap
+        assert_eq!(budget.admit(10), Admit::RunFull);
+        assert!(budget.run_full());
+        assert_eq!(budget.admit(1), Admit::RunFull);
+    }
+
+    #[test]
+    fn test_base_caps() {
+        let budget = Budget::new(Caps::default(), Caps { lines: Some(2), ..Caps::default() }).unwrap();
+        budget.start_base();
+        assert_eq!(budget.admit(10), Admit::Yes);
+        assert_eq!(budget.admit(10), Admit::Yes);
+        assert_eq!(budget.admit(10), Admit::BaseFull);
+        assert_eq!(budget.admit(10), Admit::BaseFull);
+
+        budget.start_base();
+        assert_eq!(budget.admit(10), Admit::Yes);
+        assert_eq!(budget.truncated_bases(), 1);
+        assert!(!budget.run_full());
+        assert!(budget.reached());
+    }
+
+    #[test]
+    fn test_time_caps() {
+        let budget = Budget::new(Caps::default(), Caps { duration: Some(Duration::from_millis(20)), ..Caps::default() }).unwrap();
+        budget.start_base();
+        assert_eq!(budget.check_time(), Admit::Yes);
+        std::thread::sleep(Duration::from_millis(30));
+        // Reached without any line being admitted
+        assert_eq!(budget.check_time(), Admit::BaseFull);
+        assert_eq!(budget.truncated_bases(), 1);
+
+        budget.start_base();
+        assert_eq!(budget.admit(10), Admit::Yes);
+    }
+
+    #[test]
+    fn test_unlimited() {
+        assert!(Budget::new(Caps::default(), Caps::default()).is_none());
+    }
+}
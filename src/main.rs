@@ -1,21 +1,66 @@
+mod arena;
 mod io_utils;
 mod rr;
 mod generator;
+mod model;
+mod extract;
+mod dns;
+mod psl;
+mod scope;
+mod bloom;
+mod exclude;
+mod filter;
+mod dedupe;
+mod progress;
+mod stats;
+mod limits;
+mod manifest;
+mod sink;
 
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use colored::*;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::io::{self, IsTerminal, Write};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, IsTerminal, Write};
+use std::time::{Duration, Instant};
 
 #[derive(Parser)]
 #[command(
     name = "mksub-rs",
     about = "Generate subdomains by prepending wordlist entries to base domains up to a specified depth. Optimized for very large wordlists and high fan-out.",
     long_about = "Generate subdomains by prepending wordlist entries to base domains up to a specified depth.\nOptimized for very large wordlists and high fan-out.\n\nDeveloped by: https://robensive.in",
-    version
+    version,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
 )]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Generation options, used when no subcommand is given
+    #[command(flatten)]
+    args: Option<Args>,
+
+    /// Disable colored output
+    #[arg(long = "no-color", short = 'n', global = true)]
+    no_color: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate subdomains (the default when no subcommand is given)
+    Generate(Box<Args>),
+
+    /// Learn a label transition model from known hostnames
+    Train(TrainArgs),
+
+    /// Build a ranked wordlist from the subdomain labels of known hostnames or URLs
+    ExtractWords(ExtractArgs),
+}
+
+#[derive(clap::Args)]
 struct Args {
     /// Single base domain (e.g., example.com)
     #[arg(short, long)]
@@ -25,6 +70,19 @@ struct Args {
     #[arg(long = "domain-file")]
     domain_file: Option<String>,
 
+    /// Read base domains from stdin and generate for each as it arrives, instead of loading
+    /// them all first (for long-running recon pipelines)
+    #[arg(long, conflicts_with_all = ["domain", "domain_file"])]
+    stream: bool,
+
+    /// Generate under each host as given, its registrable domain (eTLD+1), or both
+    #[arg(long = "base-mode", value_enum, default_value = "host")]
+    base_mode: psl::BaseMode,
+
+    /// Public Suffix List file to use instead of the embedded snapshot
+    #[arg(long = "psl-file")]
+    psl_file: Option<String>,
+
     /// Wordlist file (one token per line)
     #[arg(short, long)]
     wordlist: String,
@@ -37,6 +95,27 @@ struct Args {
     #[arg(short, long, default_value = "1")]
     level: u32,
 
+    /// Lowest depth to emit. Shallower depths are still traversed but not written
+    #[arg(long = "min-level", default_value = "1")]
+    min_level: u32,
+
+    /// Emit only this exact depth (shorthand for --min-level k --level k)
+    #[arg(long = "only-level", conflicts_with_all = ["level", "min_level"])]
+    only_level: Option<u32>,
+
+    /// Emit names in descending joint probability of their word weights (best-first, single-threaded)
+    #[arg(long)]
+    ranked: bool,
+
+    /// Stop after the N most likely names per base domain, counting only names that pass
+    /// the output filters. Implies --ranked
+    #[arg(long)]
+    top: Option<usize>,
+
+    /// Label transition model from `train`. Orders output by chain plausibility. Implies --ranked
+    #[arg(long)]
+    model: Option<String>,
+
     /// Concurrency per level (throttle)
     #[arg(short, long, default_value = "100")]
     threads: usize,
@@ -45,6 +124,13 @@ struct Args {
     #[arg(short, long)]
     output: Option<String>,
 
+    /// Output destination, repeatable: stdout, file=PATH, gzip=PATH or null, with an optional
+    /// format (plain, jsonl), e.g. stdout:jsonl or file:jsonl=PATH.
+    /// Replaces --output and --silent; e.g. --sink file=results.txt --sink stdout saves shards
+    /// and streams to the next tool
+    #[arg(long = "sink", value_name = "SPEC", conflicts_with_all = ["output", "silent"])]
+    sinks: Vec<sink::SinkSpec>,
+
     /// Skip writing to stdout (faster). Automatically set to false when --output is omitted
     #[arg(long, default_value = "true")]
     silent: bool,
@@ -53,6 +139,38 @@ struct Args {
     #[arg(long, default_value = "1")]
     shards: usize,
 
+    /// Write one file per base domain into the output directory (DIR/example.com.txt)
+    /// instead of round-robin shard files
+    #[arg(long = "per-base", conflicts_with_all = ["rotate_size", "rotate_lines"])]
+    per_base: bool,
+
+    /// Most per-base files kept open at once per shard; older ones are closed and reopened
+    /// for appending when needed
+    #[arg(long = "max-open-files", default_value = "256", requires = "per_base")]
+    max_open_files: usize,
+
+    /// Start a new numbered file once a shard file reaches this size (e.g. 1G)
+    #[arg(long = "rotate-size", value_parser = limits::parse_size)]
+    rotate_size: Option<u64>,
+
+    /// Start a new numbered file once a shard file holds this many lines
+    #[arg(long = "rotate-lines")]
+    rotate_lines: Option<u64>,
+
+    /// Write a manifest of output files, checksums, arguments and input hashes. Without a
+    /// PATH it goes to NAME.manifest.json next to the first output file. Always on when rotating
+    #[arg(long, value_name = "PATH", num_args = 0..=1)]
+    manifest: Option<Option<String>>,
+
+    /// Append to existing output files instead of replacing them. Rotation continues from
+    /// the last numbered part
+    #[arg(long, conflicts_with = "no_clobber")]
+    append: bool,
+
+    /// Refuse to write over output files that already exist
+    #[arg(long = "no-clobber")]
+    no_clobber: bool,
+
     /// Writer buffer flush threshold in MiB (per shard)
     #[arg(long = "buffer-mb", default_value = "100")]
     buffer_mb: usize,
@@ -69,28 +187,329 @@ struct Args {
     #[arg(long = "ci-regex", default_value = "true")]
     ci_regex: bool,
 
-    /// Disable colored output
-    #[arg(long = "no-color", short = 'n')]
-    no_color: bool,
+    /// How to handle wordlist entries that are not valid DNS labels
+    #[arg(long = "invalid-words", value_enum, default_value = "drop")]
+    invalid_words: dns::InvalidAction,
+
+    /// Accept underscores in wordlist labels (SRV-style names like _sip._tcp)
+    #[arg(long = "allow-underscore")]
+    allow_underscore: bool,
+
+    /// Emit internationalized names in Unicode instead of their xn-- (punycode) form
+    #[arg(long)]
+    unicode: bool,
+
+    /// Scope rules checked on every generated name: exact names, *.wildcards or /regex/,
+    /// prefixed with ! to exclude
+    #[arg(long = "scope-file")]
+    scope_file: Option<String>,
+
+    /// Known hostnames to leave out of the output (e.g. already resolved hosts)
+    #[arg(long = "exclude-file")]
+    exclude_file: Option<String>,
+
+    /// Hold excluded names in a Bloom filter instead of a set of 64-bit hashes (for huge files)
+    #[arg(long = "exclude-bloom", requires = "exclude_file")]
+    exclude_bloom: bool,
+
+    /// False-positive rate of the exclude Bloom filter. False positives drop new names
+    #[arg(long = "exclude-fp-rate", default_value = "0.001")]
+    exclude_fp_rate: f64,
+
+    /// Only emit names matching this regex (or glob:PATTERN). Repeatable; any match keeps a name
+    #[arg(long = "match")]
+    matches: Vec<String>,
+
+    /// Drop names matching this regex (or glob:PATTERN). Repeatable
+    #[arg(long)]
+    filter: Vec<String>,
+
+    /// Only use words matching EXPR at depth LEVEL, as LEVEL=EXPR. Repeatable
+    #[arg(long = "level-match", value_parser = filter::parse_level_rule)]
+    level_match: Vec<filter::LevelRule>,
+
+    /// Never use words matching EXPR at depth LEVEL, as LEVEL=EXPR. Repeatable
+    #[arg(long = "level-filter", value_parser = filter::parse_level_rule)]
+    level_filter: Vec<filter::LevelRule>,
+
+    /// Drop names already emitted in this run (overlapping bases, combined modes)
+    #[arg(long, value_enum)]
+    dedupe: Option<dedupe::DedupeMode>,
+
+    /// False-positive rate of the bloom dedupe filter. False positives drop new names
+    #[arg(long = "dedupe-fp-rate", default_value = "0.0001")]
+    dedupe_fp_rate: f64,
+
+    /// Expected number of emitted names for sizing the bloom dedupe filter.
+    /// Defaults to the computed combination count
+    #[arg(long = "dedupe-capacity")]
+    dedupe_capacity: Option<usize>,
+
+    /// Largest bloom dedupe filter to allocate, in MiB. Runs needing more stop with an error
+    /// asking for --dedupe-capacity
+    #[arg(long = "dedupe-max-mb", default_value = "1024")]
+    dedupe_max_mb: u64,
+
+    /// Progress on stderr: live when it is a terminal, or plain lines for logs
+    #[arg(long, value_enum, default_value = "auto")]
+    progress: progress::ProgressMode,
+
+    /// Seconds between plain progress lines
+    #[arg(long = "progress-interval", default_value = "10")]
+    progress_interval: u64,
+
+    /// Stop after writing this many names in total
+    #[arg(long = "max-lines")]
+    max_lines: Option<u64>,
+
+    /// Stop before writing more than this many bytes in total (e.g. 500M, 2G)
+    #[arg(long = "max-bytes", value_parser = limits::parse_size)]
+    max_bytes: Option<u64>,
+
+    /// Stop generating after this much wall time (e.g. 90s, 30m, 1h30m)
+    #[arg(long = "max-duration", value_parser = limits::parse_duration)]
+    max_duration: Option<Duration>,
+
+    /// Move on to the next base after writing this many names for the current one
+    #[arg(long = "max-lines-per-base")]
+    max_lines_per_base: Option<u64>,
+
+    /// Move on to the next base after writing this many bytes for the current one
+    #[arg(long = "max-bytes-per-base", value_parser = limits::parse_size)]
+    max_bytes_per_base: Option<u64>,
+
+    /// Move on to the next base after spending this much time on the current one
+    #[arg(long = "max-duration-per-base", value_parser = limits::parse_duration)]
+    max_duration_per_base: Option<Duration>,
+
+    /// Write run statistics (per base, per level, per filter, per shard) to this JSON file
+    #[arg(long = "stats-json")]
+    stats_json: Option<String>,
+}
+
+#[derive(clap::Args)]
+struct TrainArgs {
+    /// File with known hostnames, one per line. Reads stdin if omitted
+    #[arg(short, long)]
+    input: Option<String>,
+
+    /// Base domain the hostnames belong to. Without bases, each host's registrable domain is
+    /// stripped
+    #[arg(short, long)]
+    domain: Option<String>,
+
+    /// File with base domains, one per line
+    #[arg(long = "domain-file")]
+    domain_file: Option<String>,
+
+    /// Public Suffix List file to use instead of the embedded snapshot
+    #[arg(long = "psl-file")]
+    psl_file: Option<String>,
+
+    /// Model file to write
+    #[arg(short, long)]
+    output: String,
+}
+
+#[derive(clap::Args)]
+struct ExtractArgs {
+    /// File with hostnames or URLs, one per line. Reads stdin if omitted
+    #[arg(short, long)]
+    input: Option<String>,
+
+    /// Base domain to strip. Without bases, each host's registrable domain is stripped
+    #[arg(short, long)]
+    domain: Option<String>,
+
+    /// File with base domains, one per line
+    #[arg(long = "domain-file")]
+    domain_file: Option<String>,
+
+    /// Public Suffix List file to use instead of the embedded snapshot
+    #[arg(long = "psl-file")]
+    psl_file: Option<String>,
+
+    /// Write the wordlist to file instead of stdout
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// Drop tokens seen fewer than this many times
+    #[arg(long = "min-count", default_value = "1")]
+    min_count: u64,
+
+    /// Drop tokens shorter than this many characters
+    #[arg(long = "min-length", default_value = "2")]
+    min_length: usize,
+
+    /// Write bare tokens instead of `token count` lines
+    #[arg(long = "no-counts")]
+    no_counts: bool,
 }
 
 static SHUTDOWN: AtomicBool = AtomicBool::new(false);
 
 fn main() -> Result<()> {
-    let mut args = Args::parse();
+    let cli = Cli::parse();
 
     // Initialize colored output based on args and TTY detection
-    if args.no_color || !io::stderr().is_terminal() {
+    if cli.no_color || !io::stderr().is_terminal() {
         colored::control::set_override(false);
     } else {
         colored::control::set_override(true);
     }
 
+    match cli.command {
+        Some(Command::Train(args)) => run_train(args),
+        Some(Command::ExtractWords(args)) => run_extract(args),
+        Some(Command::Generate(args)) => run_generate(*args),
+        None => run_generate(cli.args.context("Missing generation arguments")?),
+    }
+}
+
+/// Train a label transition model and write it to disk
+fn run_train(args: TrainArgs) -> Result<()> {
+    let bases = if args.domain.is_some() || args.domain_file.is_some() {
+        io_utils::read_domains(args.domain.as_deref(), args.domain_file.as_deref(), None)?.bases
+    } else {
+        Vec::new()
+    };
+    let suffixes = load_suffixes(args.psl_file.as_deref())?;
+
+    let (model, used) = if let Some(ref path) = args.input {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open hostname file: {}", path))?;
+        model::train(BufReader::new(file), &bases, &suffixes)?
+    } else {
+        if io::stdin().is_terminal() {
+            eprintln!("{} Must provide --input or pipe hostnames via stdin", "Error:".red().bold());
+            std::process::exit(1);
+        }
+        model::train(io::stdin().lock(), &bases, &suffixes)?
+    };
+
+    if model.is_empty() {
+        eprintln!("{} No usable hostnames found", "Error:".red().bold());
+        std::process::exit(1);
+    }
+
+    model.save(&args.output)?;
+
+    eprintln!(
+        "{} {} {} transitions from {} hostnames to {}",
+        "🧠".bright_blue(),
+        "Learned".bright_green().bold(),
+        model.len().to_string().bright_cyan().bold(),
+        used.to_string().bright_cyan().bold(),
+        args.output.bright_cyan()
+    );
+    Ok(())
+}
+
+/// Extract ranked word tokens from known hostnames
+fn run_extract(args: ExtractArgs) -> Result<()> {
+    let bases = if args.domain.is_some() || args.domain_file.is_some() {
+        io_utils::read_domains(args.domain.as_deref(), args.domain_file.as_deref(), None)?.bases
+    } else {
+        Vec::new()
+    };
+    let suffixes = load_suffixes(args.psl_file.as_deref())?;
+
+    let (words, used) = if let Some(ref path) = args.input {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open hostname file: {}", path))?;
+        extract::extract_words(BufReader::new(file), &bases, &suffixes, args.min_length)?
+    } else {
+        if io::stdin().is_terminal() {
+            eprintln!("{} Must provide --input or pipe hostnames via stdin", "Error:".red().bold());
+            std::process::exit(1);
+        }
+        extract::extract_words(io::stdin().lock(), &bases, &suffixes, args.min_length)?
+    };
+
+    let mut writer: Box<dyn Write> = match args.output {
+        Some(ref path) => Box::new(BufWriter::new(
+            File::create(path).with_context(|| format!("Failed to create output file: {}", path))?,
+        )),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+
+    let mut written = 0usize;
+    for (word, count) in words.iter().filter(|(_, count)| *count >= args.min_count) {
+        if args.no_counts {
+            writeln!(writer, "{}", word)?;
+        } else {
+            writeln!(writer, "{} {}", word, count)?;
+        }
+        written += 1;
+    }
+    writer.flush().context("Failed to write wordlist")?;
+
+    eprintln!(
+        "{} {} {} words from {} hostnames",
+        "📝".bright_blue(),
+        "Extracted".bright_green().bold(),
+        written.to_string().bright_cyan().bold(),
+        used.to_string().bright_cyan().bold()
+    );
+    Ok(())
+}
+
+/// Generate subdomains for every base domain
+fn run_generate(mut args: Args) -> Result<()> {
+    let run_start = Instant::now();
+
     // Behavioral parity: If --output is omitted, force --silent=false
     if args.output.is_none() {
         args.silent = false;
     }
 
+    // --output and --silent are shorthand for a file sink and a stdout sink
+    let sinks = if args.sinks.is_empty() {
+        let file = args.output.as_deref().map(sink::SinkSpec::file);
+        file.into_iter().chain((!args.silent).then(sink::SinkSpec::stdout)).collect()
+    } else {
+        std::mem::take(&mut args.sinks)
+    };
+    let to_stdout = sinks.iter().any(|sink| sink.kind == sink::SinkKind::Stdout);
+    let first_file = sinks.iter().find_map(|sink| sink.path().map(str::to_string));
+    if sinks.iter().filter(|sink| sink.kind == sink::SinkKind::Stdout).count() > 1 {
+        eprintln!("{} Only one stdout sink is allowed", "Error:".red().bold());
+        std::process::exit(1);
+    }
+    let file_flags = [
+        ("--per-base", args.per_base),
+        ("--rotate-size", args.rotate_size.is_some()),
+        ("--rotate-lines", args.rotate_lines.is_some()),
+        ("--manifest", args.manifest.is_some()),
+        ("--append", args.append),
+        ("--no-clobber", args.no_clobber),
+    ];
+    if first_file.is_none()
+        && let Some((flag, _)) = file_flags.iter().find(|(_, set)| *set)
+    {
+        eprintln!("{} {} needs --output or a file sink", "Error:".red().bold(), flag);
+        std::process::exit(1);
+    }
+    if sinks.iter().any(|sink| matches!(sink.kind, sink::SinkKind::Gzip(_)))
+        && let Some((flag, _)) = file_flags
+            .iter()
+            .find(|(flag, set)| *set && ["--per-base", "--rotate-size", "--rotate-lines", "--append"].contains(flag))
+    {
+        eprintln!("{} {} is not supported with gzip sinks", "Error:".red().bold(), flag);
+        std::process::exit(1);
+    }
+
+    if let Some(k) = args.only_level {
+        args.min_level = k;
+        args.level = k;
+    }
+
+    if args.min_level == 0 || args.min_level > args.level {
+        eprintln!("{} --min-level must be between 1 and --level ({})",
+                  "Error:".red().bold(), args.level);
+        std::process::exit(1);
+    }
+
     // Check for required inputs
     if args.domain.is_none() && args.domain_file.is_none() && io::stdin().is_terminal() {
         eprintln!("{} Must provide either --domain, --domain-file, or pipe domains via stdin", 
@@ -98,9 +517,26 @@ fn main() -> Result<()> {
         std::process::exit(1);
     }
 
+    // The base count is unknown up front when streaming, so it cannot size the filter
+    if args.stream && args.dedupe == Some(dedupe::DedupeMode::Bloom) && args.dedupe_capacity.is_none() {
+        eprintln!("{} --dedupe bloom with --stream requires --dedupe-capacity",
+                  "Error:".red().bold());
+        std::process::exit(1);
+    }
+
+    // Per-base files are checked before writing starts, which needs every base up front
+    if args.stream && args.per_base && args.no_clobber {
+        eprintln!("{} --per-base with --no-clobber cannot be used with --stream",
+                  "Error:".red().bold());
+        std::process::exit(1);
+    }
+
     // Set up graceful shutdown with double Ctrl+C handling
     let shutdown_flag = Arc::new(AtomicBool::new(false));
     let shutdown_clone = shutdown_flag.clone();
+    // Stops the generator for the current base; also set by caps in the emit function
+    let cancel = Arc::new(AtomicBool::new(false));
+    let cancel_clone = cancel.clone();
     let force_exit = Arc::new(AtomicBool::new(false));
     let force_exit_clone = force_exit.clone();
     
@@ -119,6 +555,7 @@ fn main() -> Result<()> {
             );
             shutdown_clone.store(true, Ordering::Relaxed);
             SHUTDOWN.store(true, Ordering::Relaxed);
+            cancel_clone.store(true, Ordering::Relaxed);
         }
     })
     .context("Error setting Ctrl-C handler")?;
@@ -132,41 +569,157 @@ fn main() -> Result<()> {
             .context("Failed to configure rayon thread pool")?;
     }
 
-    // Read base domains
-    let bases = io_utils::read_domains(
-        args.domain.as_deref(),
-        args.domain_file.as_deref(),
-    )?;
+    // Read base domains, refusing bare public suffixes
+    let suffixes = load_suffixes(args.psl_file.as_deref())?;
+    let resolver = psl::BaseResolver::new(suffixes, args.base_mode);
+    let mut stream = args
+        .stream
+        .then(|| io_utils::DomainStream::new(io::stdin().lock(), Some(&resolver)));
 
-    if bases.is_empty() {
-        eprintln!("{} No valid base domains found", "Error:".red().bold());
-        std::process::exit(1);
-    }
+    let (bases, base_counts) = if stream.is_some() {
+        (Vec::new(), io_utils::BaseCounts::default())
+    } else {
+        let domains = io_utils::read_domains(
+            args.domain.as_deref(),
+            args.domain_file.as_deref(),
+            Some(&resolver),
+        )?;
+        report_base_counts(domains.counts);
+
+        if domains.bases.is_empty() {
+            eprintln!("{} No valid base domains found", "Error:".red().bold());
+            std::process::exit(1);
+        }
+        (domains.bases, domains.counts)
+    };
 
     // Read and process wordlist
     let words = io_utils::read_wordlist(
         &args.wordlist,
         args.regex.as_deref(),
         args.ci_regex,
+        dns::LabelPolicy {
+            action: args.invalid_words,
+            allow_underscore: args.allow_underscore,
+        },
     )?;
 
+    if words.rejected > 0 || words.sanitized > 0 {
+        eprintln!(
+            "{} {} invalid wordlist entries dropped, {} sanitized",
+            "⚠️".yellow(),
+            words.rejected.to_string().yellow().bold(),
+            words.sanitized.to_string().yellow().bold()
+        );
+    }
+
     if words.is_empty() {
         eprintln!("{} No valid words found in wordlist", "Error:".red().bold());
         std::process::exit(1);
     }
 
+    let scope = args.scope_file.as_deref().map(scope::Scope::load).transpose()?;
+    let name_filter = filter::NameFilter::new(&args.matches, &args.filter, args.ci_regex)?;
+
+    let mut levels = generator::Levels::new(args.min_level, args.level);
+    filter::restrict_levels(
+        &mut levels,
+        &words.words,
+        &args.level_match,
+        &args.level_filter,
+        args.ci_regex,
+    )?;
+
+    let known = match args.exclude_file {
+        Some(ref path) => {
+            let fp_rate = args.exclude_bloom.then_some(args.exclude_fp_rate);
+            let (known, count) = exclude::KnownNames::load(path, fp_rate)?;
+            eprintln!(
+                "{} {} {} known names to exclude ({}, {:.1} MiB)",
+                "📚".bright_blue(),
+                "Loaded".bright_green().bold(),
+                count.to_string().bright_cyan().bold(),
+                if args.exclude_bloom { "bloom" } else { "hashed" },
+                known.size_bytes() as f64 / (1024.0 * 1024.0)
+            );
+            Some(known)
+        }
+        None => None,
+    };
+
+    // Names generated per base before any output filter
+    let per_base = levels.combinations(words.len());
+    let per_base = args.top.map_or(per_base, |top| per_base.min(top as u128));
+
+    let dedupe = args.dedupe.map(|mode| {
+        let capacity = args.dedupe_capacity.unwrap_or_else(|| {
+            usize::try_from(per_base.saturating_mul(bases.len() as u128)).unwrap_or(usize::MAX)
+        });
+        let needed = bloom::BloomFilter::size_for(capacity, args.dedupe_fp_rate);
+        if mode == dedupe::DedupeMode::Bloom && needed > args.dedupe_max_mb << 20 {
+            eprintln!(
+                "{} A bloom dedupe filter for {} names needs {} MiB, over the --dedupe-max-mb limit of {}. \
+                 Set --dedupe-capacity to the number of names you expect, or raise --dedupe-max-mb",
+                "Error:".red().bold(),
+                capacity,
+                needed >> 20,
+                args.dedupe_max_mb
+            );
+            std::process::exit(1);
+        }
+        let dedupe = dedupe::Dedupe::new(mode, capacity, args.dedupe_fp_rate);
+        if mode == dedupe::DedupeMode::Bloom {
+            eprintln!(
+                "{} Dedupe filter sized for {} names ({:.1} MiB)",
+                "🧮".bright_blue(),
+                capacity.to_string().bright_cyan().bold(),
+                dedupe.reserved_bytes() as f64 / (1024.0 * 1024.0)
+            );
+        }
+        dedupe
+    });
+
+    let ranked = args.ranked || args.top.is_some() || args.model.is_some();
+    let ranking: Option<Box<dyn generator::Ranking>> = match args.model {
+        Some(ref path) => {
+            let model = model::Model::load(path)?;
+            Some(Box::new(model::MarkovRanking::new(&model, &words.words, &words.weights)))
+        }
+        None if ranked => Some(Box::new(generator::WeightRanking::new(&words.weights, words.len()))),
+        None => None,
+    };
+
     // Print colorful status information
+    let depths = if args.min_level == args.level && args.level > 1 {
+        format!("only level {}", args.level)
+    } else if args.min_level > 1 {
+        format!("levels {}-{}", args.min_level, args.level)
+    } else {
+        format!("up to level {}", args.level)
+    };
+    let domains = if args.stream {
+        format!("{} domains", "streaming".bright_cyan().bold())
+    } else {
+        format!("{} domains", bases.len().to_string().bright_cyan().bold())
+    };
     eprintln!(
-        "{} {} {} domains and {} unique words, generating up to level {}",
+        "{} {} {} and {} unique {}words, generating {}{}",
         "🚀".bright_blue(),
         "Loaded".bright_green().bold(),
-        bases.len().to_string().bright_cyan().bold(),
+        domains,
         words.len().to_string().bright_cyan().bold(),
-        args.level.to_string().bright_magenta().bold()
+        if words.is_weighted() { "weighted " } else { "" },
+        depths.bright_magenta().bold(),
+        match args.top {
+            Some(n) => format!(" (top {} per base)", n),
+            None if args.model.is_some() => " (model-ranked)".to_string(),
+            None if ranked => " (best-first)".to_string(),
+            None => String::new(),
+        }
     );
 
-    // Show attribution when not silent
-    if !args.silent {
+    // Show attribution when writing to stdout
+    if to_stdout {
         eprintln!("{} {} {}", 
             "⚡".bright_yellow(), 
             "mksub-rs by".bright_white(),
@@ -177,51 +730,185 @@ fn main() -> Result<()> {
     // Ensure status is printed before subdomain generation starts
     let _ = io::stderr().flush();
 
-    // Initialize round-robin writers
-    let (sender, writer_handles) = rr::init_writers(
-        args.output.as_deref(),
-        args.shards,
-        args.buffer_mb,
-        args.queue,
-        args.silent,
-        shutdown_flag.clone(),
-    )?;
+    // Initialize writers for every sink
+    let rotation = sink::Rotation { max_bytes: args.rotate_size, max_lines: args.rotate_lines };
+    // Rotated parts always come with a manifest listing each one
+    if rotation.is_enabled() {
+        args.manifest.get_or_insert(None);
+    }
+    let file_options = sink::FileOptions {
+        routing: if args.per_base {
+            sink::Routing::PerBase { max_open: args.max_open_files }
+        } else {
+            sink::Routing::RoundRobin
+        },
+        rotation,
+        existing: if args.append {
+            sink::Existing::Append
+        } else if args.no_clobber {
+            sink::Existing::Refuse
+        } else {
+            sink::Existing::Overwrite
+        },
+        checksums: args.manifest.is_some(),
+    };
+    let outputs = rr::open_sinks(&sinks, &file_options, args.shards, args.buffer_mb, &bases)?;
+    let rr::Writers { sender, handles: writer_handles, monitors: shard_monitors } =
+        rr::init_writers(outputs, file_options.routing, args.queue, shutdown_flag.clone());
 
-    // Create emission function
-    let emit = |line: String| {
-        if !SHUTDOWN.load(Ordering::Relaxed) && sender.send(line).is_err() {
-            // Channel closed, writers shutting down
+    let live_progress = match args.progress {
+        progress::ProgressMode::Auto => {
+            let names_on_terminal = to_stdout && io::stdout().is_terminal();
+            io::stderr().is_terminal() && !names_on_terminal
         }
+        mode => mode == progress::ProgressMode::Live,
     };
+    let counters = stats::Counters::new(levels.max);
+    let budget = limits::Budget::new(
+        limits::Caps { lines: args.max_lines, bytes: args.max_bytes, duration: args.max_duration },
+        limits::Caps {
+            lines: args.max_lines_per_base,
+            bytes: args.max_bytes_per_base,
+            duration: args.max_duration_per_base,
+        },
+    );
+    let reporter = (live_progress || args.progress == progress::ProgressMode::Plain).then(|| {
+        progress::Reporter::start(
+            live_progress,
+            Duration::from_secs(args.progress_interval.max(1)),
+            (!args.stream).then(|| per_base.saturating_mul(bases.len() as u128)),
+            counters.generated.clone(),
+            shard_monitors.clone(),
+        )
+    });
+
+    // Records carry their base only when a per-base file or a sink format needs it
+    let tag_base = args.per_base || sinks.iter().any(|sink| sink.format != sink::Format::Plain);
 
-    // Generate subdomains
-    for base in &bases {
+    // Set once every writer has gone, e.g. when stdout is closed by `| head`
+    let writers_closed = AtomicBool::new(false);
+
+    // Create emission function; `route` names the base of each record. Returns whether the
+    // name was written, so --top counts only names that made it through the filters
+    let emit_routed = |route: &Option<Arc<str>>, line: String, level: u32| {
+        counters.record_generated(level);
+        // Time caps come before the filters, which may drop every name
+        if budget.as_ref().is_some_and(|budget| budget.check_time() != limits::Admit::Yes) {
+            cancel.store(true, Ordering::Relaxed);
+            return false;
+        }
+        if !dns::is_valid_name_len(&line) {
+            counters.too_long.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        if scope.as_ref().is_some_and(|scope| !scope.allows(&line)) {
+            return false;
+        }
+        if name_filter.as_ref().is_some_and(|filter| !filter.allows(&line)) {
+            return false;
+        }
+        if known.as_ref().is_some_and(|known| known.contains(&line)) {
+            counters.excluded.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        if dedupe.as_ref().is_some_and(|dedupe| !dedupe.first_seen(&line)) {
+            return false;
+        }
+        let line = if args.unicode { dns::to_unicode(&line) } else { line };
         if SHUTDOWN.load(Ordering::Relaxed) {
+            cancel.store(true, Ordering::Relaxed);
+            return false;
+        }
+        if budget.as_ref().is_some_and(|budget| budget.admit(line.len() as u64 + 1) != limits::Admit::Yes) {
+            cancel.store(true, Ordering::Relaxed);
+            return false;
+        }
+        if sender.send(sink::Record { base: route.clone(), line }).is_err() {
+            writers_closed.store(true, Ordering::Relaxed);
+            cancel.store(true, Ordering::Relaxed);
+            return false;
+        }
+        counters.record_emitted(level);
+        true
+    };
+
+    // Generate subdomains, pulling streamed bases only once the previous one is done
+    let mut listed = bases.iter().cloned();
+    let mut per_base_stats = Vec::new();
+    // A failed stdin read ends generation but still goes through the normal shutdown below
+    let mut stream_error = None;
+    loop {
+        if SHUTDOWN.load(Ordering::Relaxed)
+            || writers_closed.load(Ordering::Relaxed)
+            || budget.as_ref().is_some_and(|budget| budget.run_full())
+        {
             break;
         }
 
-        generator::generate_subdomains(
+        let next = match stream.as_mut().map(|stream| stream.next().transpose()) {
+            Some(Ok(next)) => next,
+            Some(Err(e)) => {
+                stream_error = Some(e);
+                break;
+            }
+            None => listed.next(),
+        };
+        let Some(base) = next else {
+            break;
+        };
+        let (generated_before, emitted_before) = (counters.generated(), counters.emitted());
+        if let Some(ref budget) = budget {
+            budget.start_base();
+        }
+        cancel.store(false, Ordering::Relaxed);
+        let route = tag_base.then(|| Arc::from(base.as_str()));
+        let emit = |line: String, level: u32| emit_routed(&route, line, level);
+
+        if let Some(ref ranking) = ranking {
+            generator::generate_ranked(
+                &base,
+                &words.words,
+                ranking.as_ref(),
+                &levels,
+                args.top,
+                &cancel,
+                emit,
+            );
+        } else {
+            generator::generate_subdomains(
+                &base,
+                &words.words,
+                &levels,
+                args.threads,
+                &cancel,
+                |line, level| {
+                    emit(line, level);
+                },
+            );
+        }
+
+        per_base_stats.push(stats::BaseReport {
+            generated: counters.generated() - generated_before,
+            emitted: counters.emitted() - emitted_before,
             base,
-            &words,
-            args.level,
-            args.threads,
-            emit,
-        );
+        });
     }
 
     // Signal completion and wait for writers
     drop(sender);
+    if let Some(reporter) = reporter {
+        reporter.finish();
+    }
     
     // Ensure stdout is flushed before printing status to stderr
     let _ = io::stdout().flush();
     
-    // Only show status messages when not writing to stdout (when silent or when output file is specified)
-    if args.silent || args.output.is_some() {
+    // Only show status messages when not writing to stdout alone
+    if first_file.is_some() || !to_stdout {
         eprintln!("{} Waiting for writers to finish...", "⏳".yellow());
     }
     
     // Wait for writers with timeout and shutdown checking
-    use std::time::{Duration, Instant};
     let start_time = Instant::now();
     let mut remaining_handles = writer_handles;
     
@@ -264,8 +951,161 @@ fn main() -> Result<()> {
         }
     }
 
-    if args.silent || args.output.is_some() {
+    let base_counts = stream.as_ref().map_or(base_counts, |stream| stream.counts());
+    if stream.is_some() {
+        report_base_counts(base_counts);
+        if per_base_stats.is_empty() && stream_error.is_none() {
+            eprintln!("{} No valid base domains found", "Error:".red().bold());
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(ref output) = first_file
+        && let Some(ref path) = args.manifest
+    {
+        let path = path.clone().unwrap_or_else(|| manifest::default_path(output));
+        let inputs = [
+            ("wordlist", Some(&args.wordlist)),
+            ("domain_file", args.domain_file.as_ref()),
+            ("model", args.model.as_ref()),
+            ("scope_file", args.scope_file.as_ref()),
+            ("exclude_file", args.exclude_file.as_ref()),
+            ("psl_file", args.psl_file.as_ref()),
+        ]
+        .into_iter()
+        .filter_map(|(role, path)| path.map(|path| manifest::InputFile::hash(role, path)))
+        .collect::<Result<Vec<_>>>()?;
+        let complete = !SHUTDOWN.load(Ordering::Relaxed)
+            && stream_error.is_none()
+            && !shard_monitors.iter().any(|monitor| monitor.failed())
+            && !budget.as_ref().is_some_and(|budget| budget.reached());
+
+        let manifest = manifest::Manifest::collect(&shard_monitors, inputs, complete);
+        manifest.write(&path)?;
+        eprintln!(
+            "{} {} {} files in {}",
+            "📋".bright_blue(),
+            "Listed".bright_green().bold(),
+            manifest.files.len().to_string().bright_cyan().bold(),
+            path.bright_cyan()
+        );
+    }
+
+    let wall_time = run_start.elapsed().as_secs_f64();
+    let shards: Vec<stats::ShardReport> = shard_monitors
+        .iter()
+        .enumerate()
+        .map(|(shard, monitor)| stats::ShardReport::new(shard, monitor))
+        .collect();
+    let bytes: u64 = shards.iter().map(|shard| shard.bytes).sum();
+
+    let run_stats = stats::RunStats {
+        version: env!("CARGO_PKG_VERSION"),
+        wall_time_secs: wall_time,
+        interrupted: SHUTDOWN.load(Ordering::Relaxed),
+        limit_reached: budget.as_ref().is_some_and(|budget| budget.run_full()),
+        truncated_bases: budget.as_ref().map_or(0, |budget| budget.truncated_bases()),
+        names_per_sec: counters.emitted() as f64 / wall_time,
+        bytes_per_sec: bytes as f64 / wall_time,
+        bases: stats::BaseSummary {
+            processed: per_base_stats.len(),
+            skipped: base_counts.skipped,
+            duplicates: base_counts.duplicates,
+            public_suffixes: base_counts.public_suffixes,
+        },
+        words: stats::WordSummary {
+            lines: words.lines,
+            loaded: words.len(),
+            rejected: words.rejected,
+            sanitized: words.sanitized,
+            filtered: words.filtered,
+            duplicates: words.duplicates,
+            weighted: words.is_weighted(),
+            memory_bytes: words.size_bytes(),
+            memory_saved_bytes: words.words.owned_size_bytes().saturating_sub(words.words.size_bytes()),
+        },
+        names: stats::NameSummary {
+            generated: counters.generated(),
+            emitted: counters.emitted(),
+            too_long: counters.too_long.load(Ordering::Relaxed),
+            filtered: name_filter.as_ref().map(|filter| filter.dropped()),
+            excluded: known.is_some().then(|| counters.excluded.load(Ordering::Relaxed)),
+            duplicates: dedupe.as_ref().map(|dedupe| dedupe.duplicates()),
+            scope: scope
+                .iter()
+                .flat_map(|scope| scope.rules())
+                .filter(|rule| !rule.include)
+                .map(|rule| stats::ScopeRuleReport {
+                    rule: rule.text.clone(),
+                    suppressed: rule.suppressed(),
+                })
+                .collect(),
+            scope_unmatched: scope
+                .as_ref()
+                .filter(|scope| scope.rules().iter().any(|rule| rule.include))
+                .map(|scope| scope.unmatched()),
+        },
+        levels: counters.levels(levels.min),
+        per_base: per_base_stats,
+        shards,
+    };
+    run_stats.print_summary(scope.is_some());
+    if let Some(ref path) = args.stats_json {
+        run_stats.write(path)?;
+    }
+
+    if let Some(e) = stream_error {
+        return Err(e.context("Failed to read base domains from stdin"));
+    }
+    let failed = shard_monitors.iter().filter(|monitor| monitor.failed()).count();
+    if failed > 0 {
+        eprintln!(
+            "{} {} of {} writers failed; output is incomplete",
+            "Error:".red().bold(),
+            failed,
+            shard_monitors.len()
+        );
+        std::process::exit(1);
+    }
+    if SHUTDOWN.load(Ordering::Relaxed) {
+        eprintln!("{} {}", "⚡".yellow(), "Generation interrupted".yellow().bold());
+        std::process::exit(130);
+    }
+
+    if first_file.is_some() || !to_stdout {
         eprintln!("{} {}", "✅".green(), "Generation complete".bright_green().bold());
     }
+
+    if budget.is_some_and(|budget| budget.reached()) {
+        std::process::exit(limits::EXIT_LIMIT_REACHED);
+    }
     Ok(())
 }
+
+/// The Public Suffix List from `--psl-file`, or the embedded snapshot
+fn load_suffixes(path: Option<&str>) -> Result<psl::SuffixList> {
+    match path {
+        Some(path) => psl::SuffixList::load(path),
+        None => psl::SuffixList::embedded(),
+    }
+}
+
+/// Warn about base domain lines that were refused or repeated
+fn report_base_counts(counts: io_utils::BaseCounts) {
+    if counts.public_suffixes > 0 {
+        eprintln!(
+            "{} {} bare public suffixes skipped (e.g. co.uk)",
+            "⚠️".yellow(),
+            counts.public_suffixes.to_string().yellow().bold()
+        );
+    }
+
+    if counts.skipped > 0 || counts.duplicates > 0 {
+        eprintln!(
+            "{} {} domain lines skipped as unparseable, {} duplicates removed",
+            "⚠️".yellow(),
+            counts.skipped.to_string().yellow().bold(),
+            counts.duplicates.to_string().yellow().bold()
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use crate::rr::ShardMonitor;
+use crate::sink::OutputFile;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Listing of every file a run wrote and what produced it, for handing the output off and
+/// checking reproducibility
+#[derive(Serialize)]
+pub struct Manifest {
+    pub version: &'static str,
+    /// Command line exactly as invoked
+    pub arguments: Vec<String>,
+    /// Seconds since the Unix epoch when the run finished
+    pub created_unix: u64,
+    /// False when Ctrl+C or a --max-* cap cut the output short
+    pub complete: bool,
+    pub inputs: Vec<InputFile>,
+    pub files: Vec<OutputFile>,
+}
+
+/// An input file identified by content
+#[derive(Serialize)]
+pub struct InputFile {
+    /// What the file was used for, e.g. `wordlist`
+    pub role: &'static str,
+    pub path: String,
+    pub bytes: u64,
+    pub sha256: String,
+}
+
+impl InputFile {
+    pub fn hash(role: &'static str, path: &str) -> Result<Self> {
+        let mut file = File::open(path)
+            .with_context(|| format!("Failed to open {} for hashing: {}", role, path))?;
+        let mut hasher = Sha256::new();
+        let bytes = io::copy(&mut file, &mut hasher)
+            .with_context(|| format!("Failed to hash {}: {}", role, path))?;
+        Ok(Self { role, path: path.to_string(), bytes, sha256: format!("{:x}", hasher.finalize()) })
+    }
+}
+
+impl Manifest {
+    /// Collect the completed files of every shard, in shard order
+    pub fn collect(shards: &[ShardMonitor], inputs: Vec<InputFile>, complete: bool) -> Self {
+        let files = shards
+            .iter()
+            .flat_map(|shard| shard.stats.files.lock().unwrap().clone())
+            .collect();
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            arguments: std::env::args().collect(),
+            created_unix: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+            complete,
+            inputs,
+            files,
+        }
+    }
+
+    pub fn write(&self, path: &str) -> Result<()> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create manifest file: {}", path))?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, self).context("Failed to serialize manifest")?;
+        writeln!(writer)?;
+        writer.flush().with_context(|| format!("Failed to write manifest file: {}", path))
+    }
+}
+
+/// Manifest location next to the output: `results.txt` -> `results.manifest.json`
+pub fn default_path(output: &str) -> String {
+    Path::new(output).with_extension("manifest.json").to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_input_hash() -> Result<()> {
+        let mut temp_file = NamedTempFile::new()?;
+        write!(temp_file, "a\nb\nc\n")?;
+        let input = InputFile::hash("wordlist", temp_file.path().to_str().unwrap())?;
+        assert_eq!(input.bytes, 6);
+        assert_eq!(input.sha256, "880553fca8fcea94e325ee2cfb48e5a985cc797f39a14cc6d3cedecfeb2ae4d2");
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_path() {
+        assert_eq!(default_path("results.txt"), "results.manifest.json");
+        assert_eq!(default_path("out/results"), "out/results.manifest.json");
+    }
+}
@@ -0,0 +1,301 @@
+use crate::arena::WordArena;
+use crate::generator::{Ranking, WeightRanking};
+use crate::io_utils;
+use crate::psl::SuffixList;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+/// Context key for the label directly left of the base domain
+const START: &str = "^";
+
+/// Header line identifying the model file format
+const HEADER: &str = "# mksub-rs label model v1";
+
+/// Additive smoothing weight. Kept at 1 so any observed transition outranks every unseen one
+const SMOOTHING: f64 = 1.0;
+
+/// Label transition counts learned from known hostnames.
+/// A transition `prev -> next` means `next` appeared directly left of `prev`.
+#[derive(Default)]
+pub struct Model {
+    transitions: HashMap<String, HashMap<String, u64>>,
+}
+
+impl Model {
+    /// Record the labels of one hostname, ordered from the base outward
+    pub fn observe(&mut self, labels: &[&str]) {
+        let mut prev = START;
+        for &label in labels {
+            *self
+                .transitions
+                .entry(prev.to_string())
+                .or_default()
+                .entry(label.to_string())
+                .or_default() += 1;
+            prev = label;
+        }
+    }
+
+    /// Number of distinct transitions
+    pub fn len(&self) -> usize {
+        self.transitions.values().map(|next| next.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transitions.is_empty()
+    }
+
+    /// Write the model as tab-separated `prev next count` lines
+    pub fn save(&self, path: &str) -> Result<()> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create model file: {}", path))?;
+        let mut writer = BufWriter::new(file);
+
+        writeln!(writer, "{}", HEADER)?;
+
+        let mut prevs: Vec<_> = self.transitions.keys().collect();
+        prevs.sort();
+        for prev in prevs {
+            let mut nexts: Vec<_> = self.transitions[prev].iter().collect();
+            nexts.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+            for (next, count) in nexts {
+                writeln!(writer, "{}\t{}\t{}", prev, next, count)?;
+            }
+        }
+
+        writer.flush().with_context(|| format!("Failed to write model file: {}", path))
+    }
+
+    /// Load a model written by `save`
+    pub fn load(path: &str) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open model file: {}", path))?;
+        let reader = BufReader::new(file);
+        let mut model = Model::default();
+
+        for (lineno, line) in reader.lines().enumerate() {
+            let line = line.context("Failed to read line from model file")?;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split('\t');
+            let (Some(prev), Some(next), Some(count), None) =
+                (fields.next(), fields.next(), fields.next(), fields.next())
+            else {
+                anyhow::bail!("Malformed model line {} in {}", lineno + 1, path);
+            };
+            let count: u64 = count
+                .parse()
+                .with_context(|| format!("Invalid count on model line {} in {}", lineno + 1, path))?;
+
+            *model
+                .transitions
+                .entry(prev.to_string())
+                .or_default()
+                .entry(next.to_string())
+                .or_default() += count;
+        }
+
+        Ok(model)
+    }
+}
+
+/// Train a model from hostnames or URLs, one per line. Labels left of the longest matching base
+/// are observed; without bases the registrable domain from `suffixes` is the base.
+/// Returns the model and the number of hostnames that contributed to it.
+pub fn train<R: BufRead>(reader: R, bases: &[String], suffixes: &SuffixList) -> Result<(Model, usize)> {
+    let mut model = Model::default();
+    let mut used = 0usize;
+
+    for line in reader.lines() {
+        let line = line.context("Failed to read hostname")?;
+        let Some(host) = io_utils::extract_host(&line) else {
+            continue;
+        };
+        let host = host.to_lowercase();
+
+        let Some(prefix) = io_utils::strip_base(&host, bases, suffixes) else {
+            continue;
+        };
+
+        let labels: Vec<&str> = prefix.rsplit('.').filter(|l| !l.is_empty()).collect();
+        if !labels.is_empty() {
+            model.observe(&labels);
+            used += 1;
+        }
+    }
+
+    Ok((model, used))
+}
+
+/// Per-context successor table for `MarkovRanking`
+struct Successors {
+    /// Observed next words with log probabilities, most likely first
+    observed: Vec<(u32, f64)>,
+    /// Unigram ranks of the observed words, ascending, skipped when falling back to the unigram order
+    skipped: Vec<usize>,
+    /// Log of the probability mass left for unobserved words
+    backoff: f64,
+}
+
+/// Ranking driven by a `Model`: P(next | prev) = (c(prev, next) + a * u(next)) / (c(prev) + a),
+/// where u is the wordlist's weight-based unigram probability
+pub struct MarkovRanking {
+    unigram: WeightRanking,
+    contexts: HashMap<Option<u32>, Successors>,
+}
+
+impl MarkovRanking {
+    pub fn new(model: &Model, words: &WordArena, weights: &[u64]) -> Self {
+        let unigram = WeightRanking::new(weights, words.len());
+
+        let mut rank_of = vec![0usize; words.len()];
+        for (rank, &word) in unigram.order().iter().enumerate() {
+            rank_of[word as usize] = rank;
+        }
+        let index: HashMap<&str, u32> = words
+            .iter()
+            .enumerate()
+            .map(|(i, w)| (w, i as u32))
+            .collect();
+
+        let mut contexts = HashMap::new();
+        for (prev, nexts) in &model.transitions {
+            let key = if prev == START {
+                None
+            } else {
+                match index.get(prev.as_str()) {
+                    Some(&i) => Some(i),
+                    None => continue,
+                }
+            };
+
+            let known: Vec<(u32, u64)> = nexts
+                .iter()
+                .filter_map(|(next, &count)| index.get(next.as_str()).map(|&i| (i, count)))
+                .collect();
+            if known.is_empty() {
+                continue;
+            }
+
+            let total = known.iter().map(|&(_, c)| c as f64).sum::<f64>() + SMOOTHING;
+            let mut observed: Vec<(u32, f64)> = known
+                .iter()
+                .map(|&(word, count)| {
+                    let unigram_p = unigram.log_prob(rank_of[word as usize]).exp();
+                    (word, ((count as f64 + SMOOTHING * unigram_p) / total).ln())
+                })
+                .collect();
+            observed.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+            let mut skipped: Vec<usize> = observed.iter().map(|&(w, _)| rank_of[w as usize]).collect();
+            skipped.sort_unstable();
+
+            contexts.insert(
+                key,
+                Successors { observed, skipped, backoff: (SMOOTHING / total).ln() },
+            );
+        }
+
+        Self { unigram, contexts }
+    }
+}
+
+impl Ranking for MarkovRanking {
+    fn successor(&self, prev: Option<u32>, k: usize) -> Option<(u32, f64)> {
+        let Some(ctx) = self.contexts.get(&prev) else {
+            return self.unigram.successor(None, k);
+        };
+
+        if let Some(&hit) = ctx.observed.get(k) {
+            return Some(hit);
+        }
+
+        // k-th unobserved word in unigram order
+        let mut rank = k - ctx.observed.len();
+        for &skip in &ctx.skipped {
+            if skip <= rank {
+                rank += 1;
+            } else {
+                break;
+            }
+        }
+
+        self.unigram
+            .order()
+            .get(rank)
+            .map(|&word| (word, self.unigram.log_prob(rank) + ctx.backoff))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{generate_ranked, Levels};
+    use std::io::Cursor;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Mutex;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_train_and_roundtrip() -> Result<()> {
+        let corpus = "api.prod.example.com\nhttps://API.prod.example.com./login\nwww.example.com\n\nfoo.other.org\n";
+        let bases = vec!["example.com".to_string()];
+        let (model, used) = train(Cursor::new(corpus), &bases, &SuffixList::embedded()?)?;
+
+        assert_eq!(used, 3);
+        assert_eq!(model.transitions["^"]["prod"], 2);
+        assert_eq!(model.transitions["^"]["www"], 1);
+        assert_eq!(model.transitions["prod"]["api"], 2);
+
+        let file = NamedTempFile::new()?;
+        let path = file.path().to_str().unwrap();
+        model.save(path)?;
+        let loaded = Model::load(path)?;
+        assert_eq!(loaded.transitions, model.transitions);
+        assert_eq!(loaded.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_markov_favours_observed_chain() {
+        let mut model = Model::default();
+        for _ in 0..5 {
+            model.observe(&["prod", "api"]);
+        }
+
+        let words: WordArena = ["api", "prod", "www"].into_iter().collect();
+        let ranking = MarkovRanking::new(&model, &words, &[]);
+        let results = Mutex::new(Vec::new());
+
+        generate_ranked("example.com", &words, &ranking, &Levels::new(2, 2), None, &AtomicBool::new(false), |line, _| {
+            results.lock().unwrap().push(line);
+            true
+        });
+
+        let results = results.into_inner().unwrap();
+        assert_eq!(results.len(), 9);
+        assert_eq!(results[0], "api.prod.example.com");
+        let pos = |name: &str| results.iter().position(|r| r == name).unwrap();
+        assert!(pos("api.prod.example.com") < pos("prod.api.example.com"));
+    }
+
+    #[test]
+    fn test_markov_successor_skips_observed() {
+        let mut model = Model::default();
+        model.observe(&["b"]);
+
+        let words: WordArena = ["a", "b", "c"].into_iter().collect();
+        let ranking = MarkovRanking::new(&model, &words, &[3, 2, 1]);
+
+        let order: Vec<u32> = (0..4)
+            .filter_map(|k| ranking.successor(None, k))
+            .map(|(w, _)| w)
+            .collect();
+        assert_eq!(order, vec![1, 0, 2]);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::rr::ShardMonitor;
+use clap::ValueEnum;
+use colored::*;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Redraw interval of the live display
+const LIVE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How progress is reported on stderr during generation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ProgressMode {
+    /// Live display when stderr is a terminal (and not shared with terminal output), else off
+    Auto,
+    /// A single status line redrawn in place
+    Live,
+    /// A plain status line every --progress-interval seconds, for logs
+    Plain,
+    Off,
+}
+
+/// Background thread printing generation progress until `finish` is called
+pub struct Reporter {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+    live: bool,
+}
+
+impl Reporter {
+    /// Start reporting. `generated` counts names produced by the generator, before any filter;
+    /// `total` is the expected number of such names when known up front
+    pub fn start(
+        live: bool,
+        interval: Duration,
+        total: Option<u128>,
+        generated: Arc<AtomicU64>,
+        shards: Vec<ShardMonitor>,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_clone = stop.clone();
+        let interval = if live { LIVE_INTERVAL } else { interval };
+
+        let handle = thread::spawn(move || {
+            let start = Instant::now();
+            let mut last = (start, 0u64);
+
+            while !sleep_unless_stopped(interval, &stop_clone) {
+                let now = Instant::now();
+                let done = generated.load(Ordering::Relaxed);
+                let rate = (done - last.1) as f64 / now.duration_since(last.0).as_secs_f64().max(1e-3);
+                last = (now, done);
+
+                let line = status_line(done, total, rate, start.elapsed(), &shards);
+                if live {
+                    eprint!("\r\x1b[2K{}", line);
+                } else {
+                    eprintln!("{} {}", "⏳".yellow(), line);
+                }
+                let _ = io::stderr().flush();
+            }
+        });
+
+        Self { stop, handle, live }
+    }
+
+    /// Stop reporting and clear the live line
+    pub fn finish(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.handle.join();
+        if self.live {
+            eprint!("\r\x1b[2K");
+            let _ = io::stderr().flush();
+        }
+    }
+}
+
+/// Sleep for `interval` in short steps, returning true as soon as `stop` is set
+fn sleep_unless_stopped(interval: Duration, stop: &AtomicBool) -> bool {
+    let deadline = Instant::now() + interval;
+    while Instant::now() < deadline {
+        if stop.load(Ordering::Relaxed) {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(50).min(interval));
+    }
+    stop.load(Ordering::Relaxed)
+}
+
+fn status_line(done: u64, total: Option<u128>, rate: f64, elapsed: Duration, shards: &[ShardMonitor]) -> String {
+    let written: u64 = shards.iter().map(|s| s.stats.bytes.load(Ordering::Relaxed)).sum();
+    let queues: Vec<String> = shards.iter().map(|s| s.queued().to_string()).collect();
+
+    let mut line = String::new();
+    if let Some(total) = total.filter(|&t| t > 0) {
+        let fraction = (done as f64 / total as f64).min(1.0);
+        line.push_str(&format!("{:5.1}% | ", fraction * 100.0));
+    }
+    line.push_str(&format!(
+        "{} names | {}/s | {} written | {} elapsed",
+        format_count(done as f64),
+        format_count(rate),
+        format_bytes(written),
+        format_duration(elapsed)
+    ));
+
+    // ETA from the average rate, which is steadier than the last interval
+    if let Some(total) = total {
+        let average = done as f64 / elapsed.as_secs_f64().max(1e-3);
+        if average > 0.0 {
+            let remaining = (total as f64 - done as f64).max(0.0) / average;
+            line.push_str(&format!(" | ETA {}", format_duration(Duration::from_secs_f64(remaining.min(1e9)))));
+        }
+    }
+    line.push_str(&format!(" | queues {}", queues.join("/")));
+    line
+}
+
+/// `1234567` as `1.2M`
+fn format_count(n: f64) -> String {
+    match n {
+        n if n >= 1e9 => format!("{:.1}G", n / 1e9),
+        n if n >= 1e6 => format!("{:.1}M", n / 1e6),
+        n if n >= 1e3 => format!("{:.1}K", n / 1e3),
+        n => format!("{:.0}", n),
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    let bytes = bytes as f64;
+    match bytes {
+        b if b >= 1024.0 * 1024.0 * 1024.0 => format!("{:.1} GiB", b / (1024.0 * 1024.0 * 1024.0)),
+        b if b >= 1024.0 * 1024.0 => format!("{:.1} MiB", b / (1024.0 * 1024.0)),
+        b if b >= 1024.0 => format!("{:.1} KiB", b / 1024.0),
+        b => format!("{:.0} B", b),
+    }
+}
+
+/// `3723s` as `1h02m03s`
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{}h{:02}m{:02}s", h, m, s)
+    } else if m > 0 {
+        format!("{}m{:02}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formatting() {
+        assert_eq!(format_count(999.0), "999");
+        assert_eq!(format_count(1_234_567.0), "1.2M");
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(3 * 1024 * 1024 / 2), "1.5 MiB");
+        assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+        assert_eq!(format_duration(Duration::from_secs(3723)), "1h02m03s");
+    }
+
+    #[test]
+    fn test_status_line() {
+        let line = status_line(500, Some(1000), 100.0, Duration::from_secs(5), &[]);
+        assert!(line.starts_with(" 50.0% | 500 names | 100/s"), "{}", line);
+        assert!(line.contains("ETA 5s"), "{}", line);
+
+        let line = status_line(500, None, 100.0, Duration::from_secs(5), &[]);
+        assert!(!line.contains('%') && !line.contains("ETA"), "{}", line);
+    }
+}
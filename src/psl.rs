@@ -0,0 +1,127 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use publicsuffix::{List, Psl};
+
+/// Snapshot of https://publicsuffix.org/list/public_suffix_list.dat compiled into the binary
+const EMBEDDED_LIST: &str = include_str!("../data/public_suffix_list.dat");
+
+/// Which bases to generate under for each input host
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum BaseMode {
+    /// The host exactly as given
+    Host,
+    /// The host's registrable domain (eTLD+1)
+    Registrable,
+    /// Both the host and its registrable domain
+    Both,
+}
+
+/// Public Suffix List lookups for base domain handling
+pub struct SuffixList {
+    list: List,
+}
+
+impl SuffixList {
+    /// Parse the list compiled into the binary
+    pub fn embedded() -> Result<Self> {
+        let list = EMBEDDED_LIST
+            .parse()
+            .context("Failed to parse embedded public suffix list")?;
+        Ok(Self { list })
+    }
+
+    /// Load a list in the standard `public_suffix_list.dat` format
+    pub fn load(path: &str) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read public suffix list: {}", path))?;
+        let list = text
+            .parse()
+            .with_context(|| format!("Failed to parse public suffix list: {}", path))?;
+        Ok(Self { list })
+    }
+
+    /// Whether `host` is itself a listed public suffix such as `co.uk`
+    pub fn is_public_suffix(&self, host: &str) -> bool {
+        self.list
+            .suffix(host.as_bytes())
+            .is_some_and(|suffix| suffix.is_known() && suffix.as_bytes() == host.as_bytes())
+    }
+
+    /// Registrable domain (eTLD+1) of `host`, if it has one
+    pub fn registrable<'a>(&self, host: &'a str) -> Option<&'a str> {
+        let domain = self.list.domain(host.as_bytes())?;
+        let len = domain.as_bytes().len();
+        Some(&host[host.len() - len..])
+    }
+}
+
+/// Expands parsed hosts into the bases to generate under, refusing bare public suffixes
+pub struct BaseResolver {
+    list: SuffixList,
+    mode: BaseMode,
+}
+
+impl BaseResolver {
+    pub fn new(list: SuffixList, mode: BaseMode) -> Self {
+        Self { list, mode }
+    }
+
+    /// Bases for `host` according to the mode. Empty when `host` is a public suffix
+    pub fn resolve(&self, host: &str) -> Vec<String> {
+        if self.list.is_public_suffix(host) {
+            return Vec::new();
+        }
+
+        let registrable = self.list.registrable(host);
+        match self.mode {
+            BaseMode::Host => vec![host.to_string()],
+            BaseMode::Registrable => vec![registrable.unwrap_or(host).to_string()],
+            BaseMode::Both => {
+                let mut bases = vec![host.to_string()];
+                if let Some(registrable) = registrable.filter(|r| *r != host) {
+                    bases.push(registrable.to_string());
+                }
+                bases
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_suffix_guard() -> Result<()> {
+        let list = SuffixList::embedded()?;
+        assert!(list.is_public_suffix("co.uk"));
+        assert!(list.is_public_suffix("com"));
+        assert!(!list.is_public_suffix("example.co.uk"));
+        assert!(!list.is_public_suffix("localhost"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_registrable() -> Result<()> {
+        let list = SuffixList::embedded()?;
+        assert_eq!(list.registrable("api.dev.example.co.uk"), Some("example.co.uk"));
+        assert_eq!(list.registrable("app.example.com"), Some("example.com"));
+        assert_eq!(list.registrable("co.uk"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_modes() -> Result<()> {
+        let both = BaseResolver::new(SuffixList::embedded()?, BaseMode::Both);
+        assert_eq!(both.resolve("app.example.com"), vec!["app.example.com", "example.com"]);
+        assert_eq!(both.resolve("example.com"), vec!["example.com"]);
+        assert!(both.resolve("co.uk").is_empty());
+
+        let registrable = BaseResolver::new(SuffixList::embedded()?, BaseMode::Registrable);
+        assert_eq!(registrable.resolve("a.b.example.co.uk"), vec!["example.co.uk"]);
+
+        let host = BaseResolver::new(SuffixList::embedded()?, BaseMode::Host);
+        assert_eq!(host.resolve("a.example.com"), vec!["a.example.com"]);
+        Ok(())
+    }
+}
@@ -1,89 +1,272 @@
-use anyhow::Result;
+use crate::bloom::hash64;
+use crate::sink::{
+    base_filename, with_suffix, Existing, FileOptions, FileSink, GzipSink, NullSink, OutputFile, Record, Routing,
+    Sink, SinkKind, SinkSpec, StdoutSink, PARTIAL_SUFFIX,
+};
+use anyhow::{Context, Result};
 use colored::*;
 use crossbeam_channel::{bounded, Receiver, Sender};
-use std::fs::File;
-use std::io::{self, BufWriter, Write};
+use std::io;
 use std::path::Path;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
 
+/// Most records a writer hands its sink at once
+const BATCH_SIZE: usize = 1024;
+
 /// Round-robin selector for writer shards
-pub struct RoundRobin {
-    senders: Vec<Sender<String>>,
+pub struct RoundRobin<T> {
+    senders: Vec<Sender<T>>,
     counter: AtomicUsize,
 }
 
-impl RoundRobin {
-    pub fn new(senders: Vec<Sender<String>>) -> Self {
+impl<T> RoundRobin<T> {
+    pub fn new(senders: Vec<Sender<T>>) -> Self {
         Self {
             senders,
             counter: AtomicUsize::new(0),
         }
     }
 
-    pub fn next(&self) -> &Sender<String> {
+    pub fn next(&self) -> &Sender<T> {
         let index = self.counter.fetch_add(1, Ordering::Relaxed) % self.senders.len();
         &self.senders[index]
     }
 }
 
-/// Initialize writer threads and return sender for round-robin distribution
-pub fn init_writers(
-    output_path: Option<&str>,
+/// Lines and bytes a writer shard has written so far
+#[derive(Default)]
+pub struct ShardStats {
+    pub lines: AtomicU64,
+    pub bytes: AtomicU64,
+    /// Lines waiting in the shard's channel as of its last batch
+    pub queued: AtomicUsize,
+    /// A write, flush or close failed, so the shard's output is incomplete
+    pub failed: AtomicBool,
+    /// Files the shard has completed, in order
+    pub files: Mutex<Vec<OutputFile>>,
+}
+
+/// Read-only view of a running writer shard
+#[derive(Clone)]
+pub struct ShardMonitor {
+    /// Output file, when writing to one
+    pub path: Option<String>,
+    pub stats: Arc<ShardStats>,
+}
+
+impl ShardMonitor {
+    /// Lines waiting in the shard's channel
+    pub fn queued(&self) -> usize {
+        self.stats.queued.load(Ordering::Relaxed)
+    }
+
+    /// The shard stopped on an error
+    pub fn failed(&self) -> bool {
+        self.stats.failed.load(Ordering::Relaxed)
+    }
+}
+
+/// Running writer threads
+pub struct Writers {
+    /// Input of the distributor. Drop it to let the writers finish
+    pub sender: Sender<Record>,
+    pub handles: Vec<JoinHandle<()>>,
+    pub monitors: Vec<ShardMonitor>,
+}
+
+/// Open the sinks for every `--sink`. Each file destination gets `shards` sinks, one per
+/// writer thread; stdout and null get one. `bases` names the per-base files to check
+/// under `--no-clobber`
+pub fn open_sinks(
+    specs: &[SinkSpec],
+    files: &FileOptions,
     shards: usize,
     buffer_mb: usize,
-    queue_size: usize,
-    silent: bool,
-    shutdown_flag: Arc<AtomicBool>,
-) -> Result<(Sender<String>, Vec<JoinHandle<()>>)> {
-    let (main_sender, main_receiver) = bounded(queue_size);
-    let mut writer_handles = Vec::new();
-    let mut shard_senders = Vec::new();
+    bases: &[String],
+) -> Result<Vec<Vec<Box<dyn Sink>>>> {
+    // Check every target up front so one taken name doesn't leave other shards half-created
+    if files.existing == Existing::Refuse
+        && let Some(path) = taken_targets(specs, files, shards, bases)?.into_iter().next()
+    {
+        anyhow::bail!("Failed to create output file '{}': it already exists (--no-clobber)", path);
+    }
 
-    // Create writer shards
-    for shard_id in 0..shards {
-        let (shard_sender, shard_receiver) = bounded(queue_size);
-        shard_senders.push(shard_sender);
+    let buffer_size = buffer_mb * 1024 * 1024;
+    let mut outputs = Vec::new();
+    for spec in specs {
+        let sinks: Vec<Box<dyn Sink>> = match spec.kind {
+            SinkKind::Stdout => vec![Box::new(StdoutSink::new(spec.format))],
+            SinkKind::Null => vec![Box::new(NullSink::default())],
+            SinkKind::Gzip(ref path) => (0..shards)
+                .map(|shard_id| {
+                    let path = gzip_shard_filename(path, shard_id, shards);
+                    let sink = GzipSink::create(&path, shard_id, spec.format, files, buffer_size)
+                        .with_context(|| format!("Failed to create output file: {}", path))?;
+                    Ok(Box::new(sink) as Box<dyn Sink>)
+                })
+                .collect::<Result<_>>()?,
+            SinkKind::File(ref path) => {
+                if let Routing::PerBase { .. } = files.routing {
+                    std::fs::create_dir_all(path)
+                        .with_context(|| format!("Failed to create output directory: {}", path))?;
+                }
+                (0..shards)
+                    .map(|shard_id| {
+                        let path = match files.routing {
+                            Routing::RoundRobin => generate_shard_filename(path, shard_id, shards),
+                            Routing::PerBase { .. } => path.clone(),
+                        };
+                        let sink = FileSink::create(&path, shard_id, spec.format, files, buffer_size)
+                            .map_err(|(path, e)| anyhow::anyhow!("Failed to create output file '{}': {}", path, e))?;
+                        Ok(Box::new(sink) as Box<dyn Sink>)
+                    })
+                    .collect::<Result<_>>()?
+            }
+        };
+        outputs.push(sinks);
+    }
+    Ok(outputs)
+}
 
-        let output_file = output_path.map(|path| generate_shard_filename(path, shard_id, shards));
+/// Existing files the sinks could write over: shard and gzip files, every numbered part
+/// already on disk for a shard when rotating, and the file of each of `bases` per base
+fn taken_targets(specs: &[SinkSpec], files: &FileOptions, shards: usize, bases: &[String]) -> io::Result<Vec<String>> {
+    let mut candidates = Vec::new();
+    for spec in specs {
+        match spec.kind {
+            SinkKind::Gzip(ref path) => {
+                candidates.extend((0..shards).map(|shard_id| gzip_shard_filename(path, shard_id, shards)));
+            }
+            SinkKind::File(ref path) if files.routing == Routing::RoundRobin => {
+                for shard_id in 0..shards {
+                    let path = generate_shard_filename(path, shard_id, shards);
+                    if files.rotation.is_enabled() {
+                        candidates.extend(existing_parts(&path)?);
+                    } else {
+                        candidates.push(path);
+                    }
+                }
+            }
+            SinkKind::File(ref dir) => candidates.extend(bases.iter().map(|base| base_filename(dir, base))),
+            _ => {}
+        }
+    }
+    Ok(candidates.into_iter().filter(|path| Path::new(path).exists()).collect())
+}
 
-        let handle = spawn_writer_thread(
-            shard_id,
-            shard_receiver,
-            output_file,
-            buffer_mb,
-            silent,
-            shutdown_flag.clone(),
-        )?;
+/// Rotated parts of a shard file already on disk, whatever their number
+fn existing_parts(shard_path: &str) -> io::Result<Vec<String>> {
+    let path = Path::new(shard_path);
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let extension = path.extension().and_then(|e| e.to_str());
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
 
-        writer_handles.push(handle);
+    let mut parts = Vec::new();
+    for entry in entries {
+        let name = entry?.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        // STEM.NNNN.EXT, as written by part_filename
+        let number = name.strip_prefix(stem).and_then(|rest| rest.strip_prefix('.')).and_then(|rest| match extension {
+            Some(extension) => rest.strip_suffix(extension).and_then(|rest| rest.strip_suffix('.')),
+            None => Some(rest),
+        });
+        if number.is_some_and(|number| number.len() >= 4 && number.bytes().all(|b| b.is_ascii_digit())) {
+            parts.push(path.with_file_name(name).to_string_lossy().into_owned());
+        }
     }
+    parts.sort();
+    Ok(parts)
+}
 
-    // Create round-robin distributor
-    let rr = Arc::new(RoundRobin::new(shard_senders));
-    let rr_clone = rr.clone();
+/// Pick the writer for a record. Under per-base routing every record of a base goes to the
+/// same writer
+fn route<'a>(writers: &'a RoundRobin<Record>, record: &Record, routing: Routing) -> &'a Sender<Record> {
+    match (routing, &record.base) {
+        (Routing::PerBase { .. }, Some(base)) => &writers.senders[hash64(4, base) as usize % writers.senders.len()],
+        _ => writers.next(),
+    }
+}
 
-    // Spawn distributor thread
-    let distributor_handle = thread::spawn(move || {
-        while let Ok(line) = main_receiver.recv() {
-            if rr_clone.next().send(line).is_err() {
-                // Shard receiver closed, stop distributing
-                break;
-            }
+/// Start a writer thread for every sink and return the sender feeding them. `outputs` holds
+/// one group of sinks per destination; every record goes to one sink of each group
+pub fn init_writers(
+    outputs: Vec<Vec<Box<dyn Sink>>>,
+    routing: Routing,
+    queue_size: usize,
+    shutdown_flag: Arc<AtomicBool>,
+) -> Writers {
+    let (main_sender, main_receiver) = bounded::<Record>(queue_size);
+    let single = outputs.len() == 1 && outputs[0].len() == 1;
+
+    let mut writer_handles = Vec::new();
+    let mut monitors = Vec::new();
+    let mut destinations = Vec::new();
+    for group in outputs {
+        let mut senders = Vec::new();
+        for (shard_id, sink) in group.into_iter().enumerate() {
+            // A lone sink reads the main channel directly, with no distributor in between
+            let receiver = if single {
+                main_receiver.clone()
+            } else {
+                let (sender, receiver) = bounded(queue_size);
+                senders.push(sender);
+                receiver
+            };
+
+            let stats = Arc::new(ShardStats::default());
+            monitors.push(ShardMonitor {
+                path: sink.path().map(str::to_string),
+                stats: stats.clone(),
+            });
+            writer_handles.push(spawn_writer_thread(shard_id, receiver, sink, shutdown_flag.clone(), stats));
         }
-        
-        // Close all shard channels when main channel closes
-        drop(rr_clone);
-    });
+        destinations.push(RoundRobin::new(senders));
+    }
 
-    writer_handles.push(distributor_handle);
+    if !single {
+        // Spawn distributor thread, copying each record to every destination
+        let distributor_handle = thread::spawn(move || {
+            let mut destinations = destinations;
+            while let Ok(record) = main_receiver.recv() {
+                let mut record = Some(record);
+                let mut left = destinations.len();
+                // A destination whose writer has closed (e.g. stdout piped into `head`) is
+                // dropped; the others keep receiving
+                destinations.retain(|writers| {
+                    left -= 1;
+                    // The last destination takes the record itself, the rest get copies
+                    let copy = if left == 0 { record.take() } else { record.clone() };
+                    copy.is_some_and(|copy| route(writers, &copy, routing).send(copy).is_ok())
+                });
+                if destinations.is_empty() {
+                    return;
+                }
+            }
+            // Dropping the destinations closes all writer channels when the main channel closes
+        });
+        writer_handles.push(distributor_handle);
+    }
+
+    Writers { sender: main_sender, handles: writer_handles, monitors }
+}
 
-    Ok((main_sender, writer_handles))
+/// Shard filename for a gzip sink. Shard numbers go before the inner extension: results-1.txt.gz
+fn gzip_shard_filename(path: &str, shard_id: usize, total_shards: usize) -> String {
+    let inner = path.strip_suffix(".gz").unwrap_or(path);
+    format!("{}.gz", generate_shard_filename(inner, shard_id, total_shards))
 }
 
 /// Generate filename for a shard
-fn generate_shard_filename(base_path: &str, shard_id: usize, total_shards: usize) -> String {
+pub(crate) fn generate_shard_filename(base_path: &str, shard_id: usize, total_shards: usize) -> String {
     let path = Path::new(base_path);
     
     // Add .txt extension if none exists
@@ -96,133 +279,121 @@ fn generate_shard_filename(base_path: &str, shard_id: usize, total_shards: usize
     if total_shards == 1 {
         path_with_ext
     } else {
-        let path_obj = Path::new(&path_with_ext);
-        let stem = path_obj.file_stem().unwrap().to_str().unwrap();
-        let ext = path_obj.extension().map(|e| e.to_str().unwrap()).unwrap_or("txt");
-        let parent = path_obj.parent().map(|p| p.to_str().unwrap()).unwrap_or("");
-        
-        if parent.is_empty() {
-            format!("{}-{}.{}", stem, shard_id, ext)
-        } else {
-            format!("{}\\{}-{}.{}", parent, stem, shard_id, ext)
-        }
+        with_suffix(&path_with_ext, &format!("-{}", shard_id))
     }
 }
 
-/// Spawn a writer thread for a shard
+/// Spawn a writer thread that feeds one sink in batches
 fn spawn_writer_thread(
     shard_id: usize,
-    receiver: Receiver<String>,
-    output_file: Option<String>,
-    buffer_mb: usize,
-    silent: bool,
+    receiver: Receiver<Record>,
+    mut sink: Box<dyn Sink>,
     shutdown_flag: Arc<AtomicBool>,
-) -> Result<JoinHandle<()>> {
-    let handle = thread::spawn(move || {
-        let mut writer: Box<dyn Write + Send> = if let Some(ref path) = output_file {
-            match File::create(path) {
-                Ok(file) => {
-                    let buf_size = buffer_mb * 1024 * 1024;
-                    Box::new(BufWriter::with_capacity(buf_size, file))
-                }
-                Err(e) => {
-                    eprintln!("{} {}: Failed to create output file '{}': {}", 
-                             "❌".red(), 
-                             format!("Shard {}", shard_id).bright_yellow(),
-                             path.bright_cyan(), 
-                             e);
-                    return;
-                }
+    stats: Arc<ShardStats>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        // Only file writers announce themselves; stdout and null stay quiet
+        let label = sink.path().map(|_| format!("Shard {}", shard_id));
+        let report = |what: &str, e: io::Error| {
+            // A closed pipe just means the reader is done
+            if e.kind() == io::ErrorKind::BrokenPipe {
+                return;
             }
-        } else {
-            // Use a dummy writer that does nothing when writing to file only
-            Box::new(std::io::sink())
+            stats.failed.store(true, Ordering::Relaxed);
+            eprintln!("{} {}: {} error: {}", 
+                     "❌".red(), 
+                     label.as_deref().unwrap_or("Stdout").bright_yellow(), 
+                     what,
+                     e);
         };
 
-        let mut bytes_written = 0usize;
-        let flush_threshold = buffer_mb * 1024 * 1024;
-        
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        let mut failed = false;
+        let mut interrupted = false;
         loop {
             // Check shutdown flag first
             if shutdown_flag.load(Ordering::Relaxed) {
+                interrupted = true;
                 break;
             }
             
             // Use timeout to avoid blocking indefinitely
-            let line = match receiver.recv_timeout(std::time::Duration::from_millis(100)) {
-                Ok(line) => line,
-                Err(crossbeam_channel::RecvTimeoutError::Timeout) => continue,
-                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
-            };
-            
-            // Write to file if output_file is specified
-            if output_file.is_some() {
-                if let Err(e) = writeln!(writer, "{}", line) {
-                    eprintln!("{} {}: Write error: {}", 
-                             "❌".red(), 
-                             format!("Shard {}", shard_id).bright_yellow(), 
-                             e);
+            match receiver.recv_timeout(std::time::Duration::from_millis(100)) {
+                Ok(record) => batch.push(record),
+                // Nothing arriving; push out what is buffered
+                Err(crossbeam_channel::RecvTimeoutError::Timeout) => match sink.flush() {
+                    Ok(()) => {
+                        stats.queued.store(0, Ordering::Relaxed);
+                        continue;
+                    }
+                    Err(e) => {
+                        report("Flush", e);
+                        failed = true;
+                        break;
+                    }
+                },
+                // Main drops the sender on Ctrl+C too, often while this thread is waiting here
+                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => {
+                    interrupted = shutdown_flag.load(Ordering::Relaxed);
                     break;
                 }
-                bytes_written += line.len() + 1; // +1 for newline
             }
+            batch.extend(receiver.try_iter().take(BATCH_SIZE - 1));
+            // Published here rather than read from a receiver clone, which would keep the
+            // channel open after this thread exits
+            stats.queued.store(receiver.len(), Ordering::Relaxed);
 
-            // Write to stdout unless silent
-            if !silent {
-                // Add subtle coloring to generated subdomains
-                let colored_line = if line.contains('.') {
-                    let parts: Vec<&str> = line.split('.').collect();
-                    if parts.len() >= 2 {
-                        let subdomain_parts = &parts[..parts.len()-2];
-                        let domain_parts = &parts[parts.len()-2..];
-                        format!("{}.{}", 
-                               subdomain_parts.join(".").bright_blue(), 
-                               domain_parts.join(".").white())
-                    } else {
-                        line.bright_blue().to_string()
-                    }
-                } else {
-                    line.to_string()
-                };
-                println!("{}", colored_line);
-                // Force flush stdout to prevent mixing with stderr
-                let _ = io::stdout().flush();
+            if let Err(e) = sink.write_batch(&batch) {
+                report("Write", e);
+                failed = true;
+                break;
             }
+            batch.clear();
 
-            // Flush if threshold reached
-            if bytes_written >= flush_threshold {
-                if let Err(e) = writer.flush() {
-                    eprintln!("{} {}: Flush error: {}", 
-                             "❌".red(), 
-                             format!("Shard {}", shard_id).bright_yellow(), 
-                             e);
-                    break;
+            let written = sink.stats();
+            stats.lines.store(written.lines, Ordering::Relaxed);
+            stats.bytes.store(written.bytes, Ordering::Relaxed);
+        }
+
+        // Final flush on shutdown. After a failed write or an interrupt the files keep their
+        // .partial names, so truncated output is never mistaken for a finished file
+        if interrupted && let Err(e) = sink.flush() {
+            report("Flush", e);
+        }
+        if failed || interrupted {
+            match label {
+                Some(ref label) if sink.in_place() => {
+                    eprintln!("{} {}: Output extended in place up to the last flushed batch",
+                             "⚠️".yellow(),
+                             label.bright_yellow());
                 }
-                bytes_written = 0;
+                Some(ref label) => {
+                    eprintln!("{} {}: Output left under {} names",
+                             "⚠️".yellow(),
+                             label.bright_yellow(),
+                             PARTIAL_SUFFIX.bright_cyan());
+                }
+                None => {}
             }
+            return;
         }
-
-        // Final flush on shutdown
-        if let Err(e) = writer.flush() {
-            eprintln!("{} {}: Final flush error: {}", 
-                     "❌".red(), 
-                     format!("Shard {}", shard_id).bright_yellow(), 
-                     e);
+        match sink.finalize() {
+            Ok(finished) => stats.files.lock().unwrap().extend(finished),
+            Err(e) => report("Final flush", e),
         }
 
-        if output_file.is_some() {
+        if let Some(label) = label {
             eprintln!("{} {} writer finished", 
                      "✅".green(),
-                     format!("Shard {}", shard_id).bright_yellow());
+                     label.bright_yellow());
         }
-    });
-
-    Ok(handle)
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sink::{Rotation, SinkStats};
 
     #[test]
     fn test_generate_shard_filename() {
@@ -235,6 +406,178 @@ mod tests {
         assert_eq!(generate_shard_filename("output", 1, 2), "output-1.txt");
         assert_eq!(generate_shard_filename("output.json", 0, 2), "output-0.json");
         assert_eq!(generate_shard_filename("output.json", 1, 2), "output-1.json");
+        assert_eq!(generate_shard_filename("out/results.txt", 1, 2), "out/results-1.txt");
+    }
+
+    #[test]
+    fn test_interrupt_while_waiting_keeps_partial() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("out.txt").to_string_lossy().into_owned();
+        let files = FileOptions {
+            routing: Routing::RoundRobin,
+            rotation: Rotation::default(),
+            existing: Existing::Overwrite,
+            checksums: false,
+        };
+        let outputs = open_sinks(&[SinkSpec::file(&path)], &files, 1, 1, &[])?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let writers = init_writers(outputs, Routing::RoundRobin, 4, shutdown.clone());
+        writers.sender.send(Record { base: None, line: "api.example.com".to_string() })?;
+        // Let the writer take the record and block in recv_timeout again
+        thread::sleep(std::time::Duration::from_millis(30));
+        shutdown.store(true, Ordering::Relaxed);
+        drop(writers.sender);
+        for handle in writers.handles {
+            handle.join().unwrap();
+        }
+        assert!(!dir.path().join("out.txt").exists());
+        assert_eq!(std::fs::read_to_string(dir.path().join("out.txt.partial"))?, "api.example.com\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_clobber_checks_every_shard() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("out.txt").to_string_lossy().into_owned();
+        std::fs::write(dir.path().join("out-1.txt"), "taken\n")?;
+        let files = FileOptions {
+            routing: Routing::RoundRobin,
+            rotation: Rotation::default(),
+            existing: Existing::Refuse,
+            checksums: false,
+        };
+
+        assert!(open_sinks(&[SinkSpec::file(&path)], &files, 2, 1, &[]).is_err());
+        // Shard 0 was never opened
+        assert!(!dir.path().join("out-0.txt.partial").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_clobber_checks_later_parts() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("out.txt").to_string_lossy().into_owned();
+        std::fs::write(dir.path().join("out.0003.txt"), "taken\n")?;
+        // Other shards' parts and unrelated files are not ours
+        std::fs::write(dir.path().join("out-1.0001.txt"), "other\n")?;
+        std::fs::write(dir.path().join("out.backup.txt"), "other\n")?;
+        assert_eq!(existing_parts(&path)?, vec![dir.path().join("out.0003.txt").to_string_lossy().into_owned()]);
+
+        let files = FileOptions {
+            routing: Routing::RoundRobin,
+            rotation: Rotation { max_bytes: None, max_lines: Some(10) },
+            existing: Existing::Refuse,
+            checksums: false,
+        };
+        assert!(open_sinks(&[SinkSpec::file(&path)], &files, 1, 1, &[]).is_err());
+        assert!(!dir.path().join("out.0001.txt.partial").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_clobber_checks_base_files() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().to_string_lossy().into_owned();
+        std::fs::write(dir.path().join("b.com.txt"), "taken\n")?;
+        let files = FileOptions {
+            routing: Routing::PerBase { max_open: 4 },
+            rotation: Rotation::default(),
+            existing: Existing::Refuse,
+            checksums: false,
+        };
+        let bases = ["a.com".to_string(), "b.com".to_string()];
+        assert!(open_sinks(&[SinkSpec::file(&path)], &files, 2, 1, &bases).is_err());
+        assert!(open_sinks(&[SinkSpec::file(&path)], &files, 2, 1, &bases[..1]).is_ok());
+        Ok(())
+    }
+
+    /// Fails every write with the given error, e.g. BrokenPipe like stdout once the reader
+    /// has gone
+    struct FailingSink(io::ErrorKind);
+
+    impl Sink for FailingSink {
+        fn write_batch(&mut self, _records: &[Record]) -> io::Result<()> {
+            Err(self.0.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn finalize(self: Box<Self>) -> io::Result<Vec<OutputFile>> {
+            Ok(Vec::new())
+        }
+
+        fn stats(&self) -> SinkStats {
+            SinkStats::default()
+        }
+    }
+
+    #[test]
+    fn test_closed_writer_disconnects() {
+        let sink = Box::new(FailingSink(io::ErrorKind::BrokenPipe));
+        let writers = init_writers(vec![vec![sink]], Routing::RoundRobin, 4, Arc::new(AtomicBool::new(false)));
+        // Monitors must not keep the channel open once its writer has exited, or this blocks
+        let sent = (0..100).try_for_each(|_| {
+            let record = Record { base: None, line: "api.example.com".to_string() };
+            writers.sender.send_timeout(record, std::time::Duration::from_secs(5))
+        });
+        assert!(matches!(sent, Err(crossbeam_channel::SendTimeoutError::Disconnected(_))));
+        for handle in writers.handles {
+            handle.join().unwrap();
+        }
+        // A closed pipe is a normal way for a run to end
+        assert!(!writers.monitors[0].failed());
+    }
+
+    #[test]
+    fn test_closed_destination_is_dropped() {
+        let outputs: Vec<Vec<Box<dyn Sink>>> =
+            vec![vec![Box::new(FailingSink(io::ErrorKind::BrokenPipe))], vec![Box::new(NullSink::default())]];
+        let writers = init_writers(outputs, Routing::RoundRobin, 4, Arc::new(AtomicBool::new(false)));
+        for _ in 0..100 {
+            let record = Record { base: None, line: "api.example.com".to_string() };
+            writers.sender.send(record).unwrap();
+        }
+        drop(writers.sender);
+        for handle in writers.handles {
+            handle.join().unwrap();
+        }
+        // The null sink still got every record after the other writer closed
+        assert_eq!(writers.monitors[1].stats.lines.load(Ordering::Relaxed), 100);
+    }
+
+    #[test]
+    fn test_failed_writer_is_recorded() {
+        let sink = Box::new(FailingSink(io::ErrorKind::StorageFull));
+        let writers = init_writers(vec![vec![sink]], Routing::RoundRobin, 4, Arc::new(AtomicBool::new(false)));
+        let record = Record { base: None, line: "api.example.com".to_string() };
+        writers.sender.send(record).unwrap();
+        for handle in writers.handles {
+            handle.join().unwrap();
+        }
+        assert!(writers.monitors[0].failed());
+    }
+
+    #[test]
+    fn test_interrupted_writer_keeps_partial() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("out.txt").to_string_lossy().into_owned();
+        let files = FileOptions {
+            routing: Routing::RoundRobin,
+            rotation: Rotation::default(),
+            existing: Existing::Overwrite,
+            checksums: false,
+        };
+        let outputs = open_sinks(&[SinkSpec::file(&path)], &files, 1, 1, &[])?;
+        let writers = init_writers(outputs, Routing::RoundRobin, 4, Arc::new(AtomicBool::new(true)));
+        for handle in writers.handles {
+            handle.join().unwrap();
+        }
+        assert!(!dir.path().join("out.txt").exists());
+        assert!(dir.path().join("out.txt.partial").exists());
+        assert!(writers.monitors[0].stats.files.lock().unwrap().is_empty());
+        Ok(())
     }
 
     #[test]
@@ -0,0 +1,219 @@
+use crate::dns;
+use anyhow::{Context, Result};
+use regex::{Regex, RegexBuilder};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// How a scope rule matches a hostname
+enum Matcher {
+    /// The name itself
+    Exact(String),
+    /// A `*` wildcard pattern or a regex
+    Pattern(Regex),
+}
+
+/// A single include or exclude entry from a scope file
+pub struct Rule {
+    /// The entry as written in the scope file
+    pub text: String,
+    pub include: bool,
+    matcher: Matcher,
+    /// Names this rule suppressed (exclude rules only)
+    suppressed: AtomicU64,
+}
+
+impl Rule {
+    fn matches(&self, name: &str) -> bool {
+        match &self.matcher {
+            Matcher::Exact(exact) => exact == name,
+            Matcher::Pattern(re) => re.is_match(name),
+        }
+    }
+
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed.load(Ordering::Relaxed)
+    }
+}
+
+/// In-scope and out-of-scope rules evaluated on every generated name.
+/// A name passes when it matches no exclude rule and, if any include rules exist, at least one
+/// of them.
+pub struct Scope {
+    rules: Vec<Rule>,
+    has_includes: bool,
+    /// Names that matched no include rule
+    unmatched: AtomicU64,
+}
+
+impl Scope {
+    /// Load a scope file. Each line is a pattern, optionally prefixed with `!` or `-` to exclude
+    /// (or `+` to include, the default). Patterns are exact names, `*` wildcards such as
+    /// `*.example.com`, or regexes written as `/regex/` or `re:regex`. `#` starts a comment line
+    pub fn load(path: &str) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open scope file: {}", path))?;
+        let reader = BufReader::new(file);
+        let mut lines = Vec::new();
+        for line in reader.lines() {
+            lines.push(line.context("Failed to read line from scope file")?);
+        }
+        Self::parse(lines.iter().map(String::as_str))
+    }
+
+    fn parse<'a>(lines: impl Iterator<Item = &'a str>) -> Result<Self> {
+        let mut rules = Vec::new();
+
+        for line in lines {
+            let text = line.trim();
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+
+            let (include, pattern) = match text.as_bytes()[0] {
+                b'!' | b'-' => (false, text[1..].trim()),
+                b'+' => (true, text[1..].trim()),
+                _ => (true, text),
+            };
+
+            rules.push(Rule {
+                text: text.to_string(),
+                include,
+                matcher: parse_matcher(pattern)?,
+                suppressed: AtomicU64::new(0),
+            });
+        }
+
+        let has_includes = rules.iter().any(|rule| rule.include);
+        Ok(Self { rules, has_includes, unmatched: AtomicU64::new(0) })
+    }
+
+    /// Whether `name` is in scope. Suppressed names are counted against the responsible rule
+    pub fn allows(&self, name: &str) -> bool {
+        if let Some(rule) = self.rules.iter().find(|rule| !rule.include && rule.matches(name)) {
+            rule.suppressed.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+
+        if self.has_includes && !self.rules.iter().any(|rule| rule.include && rule.matches(name)) {
+            self.unmatched.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+
+        true
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Names suppressed for matching no include rule
+    pub fn unmatched(&self) -> u64 {
+        self.unmatched.load(Ordering::Relaxed)
+    }
+}
+
+fn parse_matcher(pattern: &str) -> Result<Matcher> {
+    let regex = if let Some(re) = pattern.strip_prefix("re:") {
+        re.to_string()
+    } else if pattern.len() > 1 && pattern.starts_with('/') && pattern.ends_with('/') {
+        pattern[1..pattern.len() - 1].to_string()
+    } else if pattern.contains('*') {
+        glob_to_regex(&encode_labels(pattern)?)
+    } else {
+        return Ok(Matcher::Exact(encode_labels(pattern.trim_end_matches('.'))?));
+    };
+
+    let re = RegexBuilder::new(&regex)
+        .case_insensitive(true)
+        .build()
+        .with_context(|| format!("Failed to compile scope pattern: {}", pattern))?;
+    Ok(Matcher::Pattern(re))
+}
+
+/// Lowercase a name or glob and IDNA-encode its Unicode labels, so patterns match the `xn--`
+/// names the generator emits. Labels holding a `*` are left as written
+fn encode_labels(pattern: &str) -> Result<String> {
+    let labels = pattern
+        .split('.')
+        .map(|label| {
+            if label.is_ascii() || label.contains('*') {
+                return Ok(label.to_lowercase());
+            }
+            dns::to_ascii(&label.to_lowercase())
+                .with_context(|| format!("Invalid internationalized scope pattern: {}", pattern))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(labels.join("."))
+}
+
+/// Translate a `*` wildcard into an anchored regex. `*` matches one or more characters, dots
+/// included, so `*.example.com` covers every depth below example.com but not example.com itself
+pub fn glob_to_regex(glob: &str) -> String {
+    let parts: Vec<String> = glob.split('*').map(regex::escape).collect();
+    format!("^{}$", parts.join(".+"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(lines: &[&str]) -> Scope {
+        Scope::parse(lines.iter().copied()).unwrap()
+    }
+
+    #[test]
+    fn test_glob_to_regex() {
+        let re = Regex::new(&glob_to_regex("*.example.com")).unwrap();
+        assert!(re.is_match("api.example.com"));
+        assert!(re.is_match("a.b.example.com"));
+        assert!(!re.is_match("example.com"));
+        assert!(!re.is_match("api.example.com.evil.org"));
+        assert!(!re.is_match("apiXexample.com"));
+    }
+
+    #[test]
+    fn test_include_and_exclude() {
+        let scope = scope(&[
+            "# program scope",
+            "*.example.com",
+            "exact.other.org",
+            "!*.internal.example.com",
+            "-re:^test",
+        ]);
+
+        assert!(scope.allows("api.example.com"));
+        assert!(scope.allows("exact.other.org"));
+        assert!(!scope.allows("db.internal.example.com"));
+        assert!(!scope.allows("test.example.com"));
+        assert!(!scope.allows("api.other.org"));
+        assert!(!scope.allows("api.unrelated.net"));
+
+        assert_eq!(scope.rules().len(), 4);
+        assert_eq!(scope.rules()[2].suppressed(), 1);
+        assert_eq!(scope.rules()[3].suppressed(), 1);
+        assert_eq!(scope.unmatched(), 2);
+    }
+
+    #[test]
+    fn test_exclude_only() {
+        let scope = scope(&["!/^(dev|staging)\\./"]);
+        assert!(scope.allows("api.example.com"));
+        assert!(!scope.allows("dev.example.com"));
+        assert_eq!(scope.unmatched(), 0);
+    }
+
+    #[test]
+    fn test_unicode_patterns() {
+        let scope = scope(&["*.bücher.de", "!intern.Bücher.de"]);
+        assert!(scope.allows("shop.xn--bcher-kva.de"));
+        assert!(!scope.allows("intern.xn--bcher-kva.de"));
+        assert_eq!(scope.rules()[1].suppressed(), 1);
+        assert!(!scope.allows("shop.bucher.de"));
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        assert!(Scope::parse(["re:("].into_iter()).is_err());
+    }
+}
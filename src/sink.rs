@@ -0,0 +1,1053 @@
+use colored::*;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufWriter, IsTerminal, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Files are written under this suffix and renamed into place once complete
+pub(crate) const PARTIAL_SUFFIX: &str = ".partial";
+
+/// Per-base files keep a small buffer each, since many may be open at once
+const BASE_FILE_BUFFER: usize = 256 * 1024;
+
+/// Stdout buffer; names are written in large blocks rather than line by line
+const STDOUT_BUFFER: usize = 1024 * 1024;
+
+/// A generated name on its way to the writers
+#[derive(Clone)]
+pub struct Record {
+    /// Base the name was generated under, set only when routing by base or when a sink
+    /// format records it
+    pub base: Option<Arc<str>>,
+    pub line: String,
+}
+
+/// How records are spread across writer shards
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Routing {
+    /// Each record goes to the next shard in turn
+    RoundRobin,
+    /// One file per base domain; each base is owned by one shard, which keeps at most
+    /// `max_open` files open
+    PerBase { max_open: usize },
+}
+
+/// What to do with output files that already exist
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Existing {
+    /// Replace them once the new file is complete
+    #[default]
+    Overwrite,
+    /// Add to the end of them in place; rotation continues from the last numbered part
+    Append,
+    /// Refuse to start (or to open that file) if one exists
+    Refuse,
+}
+
+/// Limits after which a shard starts a new numbered file. Unset fields never rotate
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Rotation {
+    pub max_bytes: Option<u64>,
+    pub max_lines: Option<u64>,
+}
+
+impl Rotation {
+    pub fn is_enabled(&self) -> bool {
+        self.max_bytes.is_some() || self.max_lines.is_some()
+    }
+}
+
+/// A completed output file
+#[derive(Clone, Debug, Serialize)]
+pub struct OutputFile {
+    pub path: String,
+    pub shard: usize,
+    pub lines: u64,
+    pub bytes: u64,
+    /// Hex SHA-256 of the contents, when checksums are enabled
+    pub sha256: Option<String>,
+}
+
+/// File output settings shared by all file sinks
+pub struct FileOptions {
+    pub routing: Routing,
+    pub rotation: Rotation,
+    pub existing: Existing,
+    /// Compute a SHA-256 of every file for the manifest
+    pub checksums: bool,
+}
+
+/// Lines and bytes a sink has written, before any compression
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SinkStats {
+    pub lines: u64,
+    pub bytes: u64,
+}
+
+/// An output backend. Each writer thread owns one sink and hands it records in batches
+pub trait Sink: Send {
+    fn write_batch(&mut self, records: &[Record]) -> io::Result<()>;
+
+    /// Push buffered output through, e.g. while the queue is idle
+    fn flush(&mut self) -> io::Result<()>;
+
+    /// Flush and close, returning the files that were completed
+    fn finalize(self: Box<Self>) -> io::Result<Vec<OutputFile>>;
+
+    fn stats(&self) -> SinkStats;
+
+    /// File or directory the sink writes, if any
+    fn path(&self) -> Option<&str> {
+        None
+    }
+
+    /// Writes go straight into the existing files (appending) instead of `.partial` files
+    fn in_place(&self) -> bool {
+        false
+    }
+}
+
+/// How names are written to a sink
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// One name per line
+    #[default]
+    Plain,
+    /// One JSON object per line: `{"name":"api.example.com","base":"example.com"}`
+    Jsonl,
+}
+
+impl Format {
+    /// Append one rendered record, newline included, to `buf`
+    pub fn render(self, record: &Record, buf: &mut Vec<u8>) {
+        match self {
+            Format::Plain => buf.extend_from_slice(record.line.as_bytes()),
+            Format::Jsonl => {
+                let entry = JsonEntry { name: &record.line, base: record.base.as_deref() };
+                serde_json::to_writer(&mut *buf, &entry).expect("serializing to a Vec cannot fail");
+            }
+        }
+        buf.push(b'\n');
+    }
+}
+
+#[derive(Serialize)]
+struct JsonEntry<'a> {
+    name: &'a str,
+    base: Option<&'a str>,
+}
+
+/// Where a sink writes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SinkKind {
+    Stdout,
+    /// Output file, sharded, rotated and finalized by the file options
+    File(String),
+    /// Gzip-compressed output file, one per shard
+    Gzip(String),
+    /// Discard everything; for measuring generation speed
+    Null,
+}
+
+/// One output destination from `--sink`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SinkSpec {
+    pub kind: SinkKind,
+    pub format: Format,
+}
+
+impl SinkSpec {
+    pub fn stdout() -> Self {
+        Self { kind: SinkKind::Stdout, format: Format::Plain }
+    }
+
+    pub fn file(path: &str) -> Self {
+        Self { kind: SinkKind::File(path.to_string()), format: Format::Plain }
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        match self.kind {
+            SinkKind::File(ref path) | SinkKind::Gzip(ref path) => Some(path),
+            SinkKind::Stdout | SinkKind::Null => None,
+        }
+    }
+}
+
+/// `KIND[:FORMAT][=PATH]`: `stdout`, `stdout:jsonl`, `file=results.txt`, `file:jsonl=names.jsonl`,
+/// `gzip=results.txt.gz`, `null`
+impl FromStr for SinkSpec {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (head, path) = match value.split_once('=') {
+            Some((head, path)) => (head, Some(path)),
+            None => (value, None),
+        };
+        let (kind, format) = match head.split_once(':') {
+            Some((kind, format)) => (kind, Some(format)),
+            None => (head, None),
+        };
+
+        let format = match format {
+            None | Some("plain") => Format::Plain,
+            Some("jsonl") => Format::Jsonl,
+            Some(other) => return Err(format!("unknown sink format '{}' (use plain or jsonl)", other)),
+        };
+        let kind = match (kind, path) {
+            ("stdout", None) => SinkKind::Stdout,
+            ("null", None) => SinkKind::Null,
+            ("stdout" | "null", Some(_)) => return Err(format!("the {} sink takes no path", kind)),
+            ("file", Some(path)) if !path.is_empty() => SinkKind::File(path.to_string()),
+            ("gzip", Some(path)) if !path.is_empty() => SinkKind::Gzip(path.to_string()),
+            ("file" | "gzip", _) => return Err(format!("the {0} sink needs a path: {0}=PATH", kind)),
+            (other, _) => return Err(format!("unknown sink '{}' (use stdout, file, gzip or null)", other)),
+        };
+        Ok(Self { kind, format })
+    }
+}
+
+/// Writes names to stdout through one large buffer, colored only when stdout is a terminal
+pub struct StdoutSink {
+    format: Format,
+    color: bool,
+    out: BufWriter<io::Stdout>,
+    rendered: Vec<u8>,
+    stats: SinkStats,
+}
+
+impl StdoutSink {
+    pub fn new(format: Format) -> Self {
+        let stdout = io::stdout();
+        Self {
+            format,
+            color: format == Format::Plain && stdout.is_terminal(),
+            out: BufWriter::with_capacity(STDOUT_BUFFER, stdout),
+            rendered: Vec::new(),
+            stats: SinkStats::default(),
+        }
+    }
+}
+
+impl Sink for StdoutSink {
+    fn write_batch(&mut self, records: &[Record]) -> io::Result<()> {
+        for record in records {
+            self.rendered.clear();
+            self.format.render(record, &mut self.rendered);
+            if self.color {
+                writeln!(self.out, "{}", color_line(&record.line))?;
+            } else {
+                self.out.write_all(&self.rendered)?;
+            }
+            self.stats.lines += 1;
+            self.stats.bytes += self.rendered.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    fn finalize(mut self: Box<Self>) -> io::Result<Vec<OutputFile>> {
+        self.out.flush()?;
+        Ok(Vec::new())
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.stats
+    }
+}
+
+/// Subtle coloring for a name on the terminal: labels in blue, registrable domain in white
+fn color_line(line: &str) -> String {
+    let parts: Vec<&str> = line.split('.').collect();
+    if parts.len() < 2 {
+        return line.to_string();
+    }
+    let (subdomain_parts, domain_parts) = parts.split_at(parts.len() - 2);
+    format!("{}.{}", subdomain_parts.join(".").bright_blue(), domain_parts.join(".").white())
+}
+
+/// Counts names and drops them
+#[derive(Default)]
+pub struct NullSink {
+    stats: SinkStats,
+}
+
+impl Sink for NullSink {
+    fn write_batch(&mut self, records: &[Record]) -> io::Result<()> {
+        self.stats.lines += records.len() as u64;
+        self.stats.bytes += records.iter().map(|record| record.line.len() as u64 + 1).sum::<u64>();
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn finalize(self: Box<Self>) -> io::Result<Vec<OutputFile>> {
+        Ok(Vec::new())
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.stats
+    }
+}
+
+/// Counts and optionally hashes the bytes that reach the disk
+struct Tally<W> {
+    inner: W,
+    hasher: Option<Sha256>,
+    bytes: u64,
+}
+
+impl<W: Write> Write for Tally<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        if let Some(ref mut hasher) = self.hasher {
+            hasher.update(&buf[..n]);
+        }
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writes one shard as a gzip file under its `.partial` name, renamed into place when
+/// finalized. The manifest records the compressed size and checksum
+pub struct GzipSink {
+    path: String,
+    shard: usize,
+    format: Format,
+    existing: Existing,
+    encoder: GzEncoder<Tally<BufWriter<File>>>,
+    rendered: Vec<u8>,
+    stats: SinkStats,
+}
+
+impl GzipSink {
+    /// Appending is refused: output goes to a `.partial` file that only replaces the target
+    pub fn create(path: &str, shard: usize, format: Format, files: &FileOptions, buffer_size: usize) -> io::Result<Self> {
+        if files.existing == Existing::Append {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "gzip sinks cannot append (--append)"));
+        }
+        if files.existing == Existing::Refuse && Path::new(path).exists() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "already exists (--no-clobber)"));
+        }
+        let file = File::create(partial_path(path))?;
+        let tally = Tally {
+            inner: BufWriter::with_capacity(buffer_size, file),
+            hasher: files.checksums.then(Sha256::new),
+            bytes: 0,
+        };
+        Ok(Self {
+            path: path.to_string(),
+            shard,
+            format,
+            existing: files.existing,
+            encoder: GzEncoder::new(tally, Compression::default()),
+            rendered: Vec::new(),
+            stats: SinkStats::default(),
+        })
+    }
+}
+
+impl Sink for GzipSink {
+    fn write_batch(&mut self, records: &[Record]) -> io::Result<()> {
+        self.rendered.clear();
+        for record in records {
+            self.format.render(record, &mut self.rendered);
+        }
+        self.encoder.write_all(&self.rendered)?;
+        self.stats.lines += records.len() as u64;
+        self.stats.bytes += self.rendered.len() as u64;
+        Ok(())
+    }
+
+    /// Sync flush, so everything written so far can be decompressed from the `.partial` file.
+    /// Only called when the queue is idle, which keeps the cost to the ratio small
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+
+    fn finalize(self: Box<Self>) -> io::Result<Vec<OutputFile>> {
+        let mut tally = self.encoder.finish()?;
+        tally.flush()?;
+        drop(tally.inner);
+        commit(&self.path, self.existing)?;
+        Ok(vec![OutputFile {
+            path: self.path,
+            shard: self.shard,
+            lines: self.stats.lines,
+            bytes: tally.bytes,
+            sha256: tally.hasher.map(|hasher| format!("{:x}", hasher.finalize())),
+        }])
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.stats
+    }
+
+    fn path(&self) -> Option<&str> {
+        Some(&self.path)
+    }
+}
+
+/// Where a shard writes and when it starts a new file
+struct ShardOutput {
+    path: String,
+    routing: Routing,
+    rotation: Rotation,
+    existing: Existing,
+    buffer_size: usize,
+    checksums: bool,
+}
+
+/// A shard's plain files: numbered parts, or one file per base
+enum FileSet {
+    Parts(PartWriter),
+    Bases(BaseFiles),
+}
+
+/// Sink writing one shard of a file destination
+pub struct FileSink {
+    path: String,
+    existing: Existing,
+    format: Format,
+    files: FileSet,
+    rendered: Vec<u8>,
+    /// Parts completed by rotation
+    finished: Vec<OutputFile>,
+    stats: SinkStats,
+}
+
+impl FileSink {
+    /// Open the shard's first file. With per-base routing `path` is the directory the base
+    /// files go in, opened as their names arrive. On failure, returns the path that could not
+    /// be created
+    pub fn create(
+        path: &str,
+        shard: usize,
+        format: Format,
+        files: &FileOptions,
+        buffer_size: usize,
+    ) -> std::result::Result<Self, (String, io::Error)> {
+        let output = ShardOutput {
+            path: path.to_string(),
+            routing: files.routing,
+            rotation: files.rotation,
+            existing: files.existing,
+            buffer_size,
+            checksums: files.checksums,
+        };
+        let path = output.path.clone();
+        let existing = output.existing;
+        let files = match output.routing {
+            Routing::RoundRobin => FileSet::Parts(PartWriter::create(shard, output)?),
+            Routing::PerBase { max_open } => FileSet::Bases(BaseFiles::new(shard, output, max_open)),
+        };
+        Ok(Self { path, existing, format, files, rendered: Vec::new(), finished: Vec::new(), stats: SinkStats::default() })
+    }
+}
+
+impl Sink for FileSink {
+    fn write_batch(&mut self, records: &[Record]) -> io::Result<()> {
+        for record in records {
+            self.rendered.clear();
+            self.format.render(record, &mut self.rendered);
+            match self.files {
+                FileSet::Parts(ref mut parts) => self.finished.extend(parts.write_line(&self.rendered)?),
+                FileSet::Bases(ref mut bases) => {
+                    let base = record.base.as_ref().ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidInput, "record without a base domain")
+                    })?;
+                    bases.write_line(base, &self.rendered)?;
+                }
+            }
+            self.stats.lines += 1;
+            self.stats.bytes += self.rendered.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.files {
+            FileSet::Parts(ref mut parts) => parts.flush(),
+            FileSet::Bases(ref mut bases) => bases.flush(),
+        }
+    }
+
+    fn finalize(self: Box<Self>) -> io::Result<Vec<OutputFile>> {
+        let mut files = self.finished;
+        match self.files {
+            FileSet::Parts(parts) => files.push(parts.finish()?),
+            FileSet::Bases(bases) => files.extend(bases.finish()?),
+        }
+        Ok(files)
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.stats
+    }
+
+    fn path(&self) -> Option<&str> {
+        Some(&self.path)
+    }
+
+    fn in_place(&self) -> bool {
+        self.existing == Existing::Append
+    }
+}
+
+/// One base's output file, open or not
+struct BaseFile {
+    path: String,
+    writer: Option<BufWriter<File>>,
+    hasher: Option<Sha256>,
+    lines: u64,
+    bytes: u64,
+}
+
+/// `DIR/<base>.txt` files written by one shard. At most `max_open` are open at a time; the
+/// least recently written file is closed to make room and reopened for appending if its
+/// base returns
+struct BaseFiles {
+    shard: usize,
+    output: ShardOutput,
+    max_open: usize,
+    files: HashMap<Arc<str>, BaseFile>,
+    /// Bases with an open file, least recently written first
+    open: VecDeque<Arc<str>>,
+}
+
+impl BaseFiles {
+    fn new(shard: usize, output: ShardOutput, max_open: usize) -> Self {
+        Self { shard, output, max_open: max_open.max(1), files: HashMap::new(), open: VecDeque::new() }
+    }
+
+    /// Write one rendered line, newline included
+    fn write_line(&mut self, base: &Arc<str>, line: &[u8]) -> io::Result<()> {
+        if self.files.get(base).is_none_or(|file| file.writer.is_none()) {
+            self.open_file(base)?;
+        } else if self.open.back() != Some(base) {
+            // Names arrive in runs per base, so this scan only happens when the base changes
+            if let Some(i) = self.open.iter().position(|open| open == base) {
+                self.open.remove(i);
+            }
+            self.open.push_back(base.clone());
+        }
+
+        let file = self.files.get_mut(base).expect("base file was just opened");
+        let writer = file.writer.as_mut().expect("base file was just opened");
+        writer.write_all(line)?;
+        if let Some(ref mut hasher) = file.hasher {
+            hasher.update(line);
+        }
+        file.lines += 1;
+        file.bytes += line.len() as u64;
+        Ok(())
+    }
+
+    /// Open (or reopen) the file for `base`, closing the least recently written one when at
+    /// the limit
+    fn open_file(&mut self, base: &Arc<str>) -> io::Result<()> {
+        while self.open.len() >= self.max_open {
+            let Some(oldest) = self.open.pop_front() else {
+                break;
+            };
+            if let Some(mut writer) = self.files.get_mut(&oldest).and_then(|file| file.writer.take()) {
+                writer.flush()?;
+            }
+        }
+
+        let existing = self.output.existing;
+        match self.files.entry(base.clone()) {
+            // Reopen for appending after an eviction
+            Entry::Occupied(entry) => {
+                let file = entry.into_mut();
+                let handle = OpenOptions::new().append(true).open(working_path(&file.path, existing))?;
+                file.writer = Some(BufWriter::with_capacity(BASE_FILE_BUFFER, handle));
+            }
+            Entry::Vacant(entry) => {
+                let path = base_filename(&self.output.path, base);
+                let opened = open_output(&path, existing, self.output.checksums)?;
+                entry.insert(BaseFile {
+                    path,
+                    writer: Some(BufWriter::with_capacity(BASE_FILE_BUFFER, opened.file)),
+                    hasher: opened.hasher,
+                    lines: opened.lines,
+                    bytes: opened.bytes,
+                });
+            }
+        }
+        self.open.push_back(base.clone());
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for file in self.files.values_mut() {
+            if let Some(ref mut writer) = file.writer {
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flush every file and rename it into place
+    fn finish(mut self) -> io::Result<Vec<OutputFile>> {
+        self.flush()?;
+        let mut files = Vec::with_capacity(self.files.len());
+        for file in self.files.into_values() {
+            drop(file.writer);
+            commit(&file.path, self.output.existing)?;
+            files.push(OutputFile {
+                path: file.path,
+                shard: self.shard,
+                lines: file.lines,
+                bytes: file.bytes,
+                sha256: file.hasher.map(|hasher| format!("{:x}", hasher.finalize())),
+            });
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(files)
+    }
+}
+
+/// The file a shard is currently writing, under its `.partial` name unless appending
+struct Part {
+    /// Final name, taken once the part is complete
+    path: String,
+    existing: Existing,
+    writer: BufWriter<File>,
+    hasher: Option<Sha256>,
+    lines: u64,
+    bytes: u64,
+}
+
+/// Writes a shard's lines, rotating into sequentially numbered files when limits are set
+struct PartWriter {
+    shard: usize,
+    output: ShardOutput,
+    number: usize,
+    part: Part,
+}
+
+impl PartWriter {
+    /// Open the first file. On failure, returns the path that could not be created
+    fn create(shard: usize, output: ShardOutput) -> std::result::Result<Self, (String, io::Error)> {
+        let mut number = 1;
+        // Continue in the last numbered part left by an earlier run
+        if output.existing == Existing::Append && output.rotation.is_enabled() {
+            while Path::new(&part_filename(&output.path, number + 1)).exists() {
+                number += 1;
+            }
+        }
+        let part = Self::open(&output, number)?;
+        Ok(Self { shard, output, number, part })
+    }
+
+    fn open(output: &ShardOutput, number: usize) -> std::result::Result<Part, (String, io::Error)> {
+        let path = if output.rotation.is_enabled() {
+            part_filename(&output.path, number)
+        } else {
+            output.path.clone()
+        };
+        let opened = open_output(&path, output.existing, output.checksums)
+            .map_err(|e| (working_path(&path, output.existing), e))?;
+        Ok(Part {
+            writer: BufWriter::with_capacity(output.buffer_size, opened.file),
+            hasher: opened.hasher,
+            path,
+            existing: output.existing,
+            lines: opened.lines,
+            bytes: opened.bytes,
+        })
+    }
+
+    /// Write one rendered line, newline included, first rotating if it would overflow the current file. Returns the
+    /// file that was completed by rotating, if any
+    fn write_line(&mut self, line: &[u8]) -> io::Result<Option<OutputFile>> {
+        let len = line.len() as u64;
+        let rotation = self.output.rotation;
+        let full = rotation.max_lines.is_some_and(|max| self.part.lines >= max)
+            || rotation.max_bytes.is_some_and(|max| self.part.bytes + len > max);
+
+        let mut finished = None;
+        if full && self.part.lines > 0 {
+            self.number += 1;
+            let next = Self::open(&self.output, self.number).map_err(|(_, e)| e)?;
+            finished = Some(finish_part(self.shard, std::mem::replace(&mut self.part, next))?);
+        }
+
+        let part = &mut self.part;
+        part.writer.write_all(line)?;
+        if let Some(ref mut hasher) = part.hasher {
+            hasher.update(line);
+        }
+        part.lines += 1;
+        part.bytes += len;
+        Ok(finished)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.part.writer.flush()
+    }
+
+    fn finish(self) -> io::Result<OutputFile> {
+        finish_part(self.shard, self.part)
+    }
+}
+
+/// Flush a part and rename it from its `.partial` name into place
+fn finish_part(shard: usize, mut part: Part) -> io::Result<OutputFile> {
+    part.writer.flush()?;
+    drop(part.writer);
+    commit(&part.path, part.existing)?;
+    Ok(OutputFile {
+        path: part.path,
+        shard,
+        lines: part.lines,
+        bytes: part.bytes,
+        sha256: part.hasher.map(|hasher| format!("{:x}", hasher.finalize())),
+    })
+}
+
+/// Name a file has while it is being written: `results.txt.partial`
+fn partial_path(path: &str) -> String {
+    format!("{}{}", path, PARTIAL_SUFFIX)
+}
+
+/// Where the file for `path` is written until it is complete; appends happen in place
+fn working_path(path: &str, existing: Existing) -> String {
+    match existing {
+        Existing::Append => path.to_string(),
+        Existing::Overwrite | Existing::Refuse => partial_path(path),
+    }
+}
+
+/// Move a completed file into place
+fn commit(path: &str, existing: Existing) -> io::Result<()> {
+    match existing {
+        Existing::Append => Ok(()),
+        Existing::Overwrite | Existing::Refuse => std::fs::rename(partial_path(path), path),
+    }
+}
+
+/// A newly opened output file and what it already holds
+struct Opened {
+    file: File,
+    hasher: Option<Sha256>,
+    lines: u64,
+    bytes: u64,
+}
+
+/// Open the output file for `path`. When appending, the existing content is counted and
+/// hashed so totals and checksums cover the whole file
+fn open_output(path: &str, existing: Existing, checksums: bool) -> io::Result<Opened> {
+    let mut hasher = checksums.then(Sha256::new);
+    match existing {
+        Existing::Refuse if Path::new(path).exists() => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists (--no-clobber)", path),
+        )),
+        Existing::Overwrite | Existing::Refuse => {
+            let file = File::create(partial_path(path))?;
+            Ok(Opened { file, hasher, lines: 0, bytes: 0 })
+        }
+        Existing::Append => {
+            let (mut lines, mut bytes, mut last) = (0, 0, b'\n');
+            if let Ok(previous) = File::open(path) {
+                let mut reader = io::BufReader::new(previous);
+                loop {
+                    let chunk = reader.fill_buf()?;
+                    let Some(&end) = chunk.last() else {
+                        break;
+                    };
+                    lines += chunk.iter().filter(|&&b| b == b'\n').count() as u64;
+                    bytes += chunk.len() as u64;
+                    if let Some(ref mut hasher) = hasher {
+                        hasher.update(chunk);
+                    }
+                    last = end;
+                    let len = chunk.len();
+                    reader.consume(len);
+                }
+            }
+
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            // Terminate an unfinished last line so appended names start on their own
+            if last != b'\n' {
+                file.write_all(b"\n")?;
+                lines += 1;
+                bytes += 1;
+                if let Some(ref mut hasher) = hasher {
+                    hasher.update(b"\n");
+                }
+            }
+            Ok(Opened { file, hasher, lines, bytes })
+        }
+    }
+}
+
+/// Insert `suffix` between a file's stem and extension
+pub(crate) fn with_suffix(path: &str, suffix: &str) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let name = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}{}.{}", stem, suffix, ext),
+        None => format!("{}{}", stem, suffix),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// Filename of a rotated part of a shard file: `results.0001.txt`, `results-1.0002.txt`
+fn part_filename(shard_path: &str, number: usize) -> String {
+    with_suffix(shard_path, &format!(".{:04}", number))
+}
+
+/// File of one base under `--per-base`: `DIR/<base>.txt`
+pub(crate) fn base_filename(dir: &str, base: &str) -> String {
+    Path::new(dir).join(format!("{}.txt", base)).to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sink() {
+        assert_eq!("stdout".parse(), Ok(SinkSpec::stdout()));
+        assert_eq!("file=out/results.txt".parse(), Ok(SinkSpec::file("out/results.txt")));
+        assert_eq!(
+            "file:jsonl=names.jsonl".parse(),
+            Ok(SinkSpec { kind: SinkKind::File("names.jsonl".to_string()), format: Format::Jsonl })
+        );
+        assert!("file".parse::<SinkSpec>().is_err());
+        assert!("stdout=x".parse::<SinkSpec>().is_err());
+        assert!("stdout:xml".parse::<SinkSpec>().is_err());
+        assert_eq!("null".parse::<SinkSpec>().map(|spec| spec.kind), Ok(SinkKind::Null));
+        assert_eq!(
+            "gzip=out.txt.gz".parse::<SinkSpec>().map(|spec| spec.kind),
+            Ok(SinkKind::Gzip("out.txt.gz".to_string()))
+        );
+        assert!("gzip".parse::<SinkSpec>().is_err());
+        assert!("s3=bucket".parse::<SinkSpec>().is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let record = Record { base: Some(Arc::from("example.com")), line: "api.example.com".to_string() };
+        let mut buf = Vec::new();
+        Format::Plain.render(&record, &mut buf);
+        Format::Jsonl.render(&record, &mut buf);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "api.example.com\n{\"name\":\"api.example.com\",\"base\":\"example.com\"}\n"
+        );
+    }
+
+    #[test]
+    fn test_color_line() {
+        // Built the same way, so this holds whether or not colors are on for this process
+        let expected = format!("{}.{}", "api.dev".bright_blue(), "example.com".white());
+        assert_eq!(color_line("api.dev.example.com"), expected);
+        assert_eq!(color_line("localhost"), "localhost");
+    }
+
+    #[test]
+    fn test_null_sink() -> io::Result<()> {
+        let mut sink = NullSink::default();
+        let records = ["a.example.com", "bb.example.com"].map(|line| Record { base: None, line: line.to_string() });
+        sink.write_batch(&records)?;
+        assert_eq!(sink.stats(), SinkStats { lines: 2, bytes: 29 });
+        Ok(())
+    }
+
+    #[test]
+    fn test_gzip_sink() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Read;
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("out.txt.gz").to_string_lossy().into_owned();
+        let files = FileOptions {
+            routing: Routing::RoundRobin,
+            rotation: Rotation::default(),
+            existing: Existing::Overwrite,
+            checksums: true,
+        };
+        let mut sink = Box::new(GzipSink::create(&path, 0, Format::Plain, &files, 1024)?);
+        let records = ["a", "b", "c"].map(|line| Record { base: None, line: line.to_string() });
+        sink.write_batch(&records)?;
+        assert!(!Path::new(&path).exists());
+
+        // A flushed sink's lines can be read back before it is finalized
+        sink.flush()?;
+        let mut flushed = [0; 6];
+        flate2::read::GzDecoder::new(File::open(partial_path(&path))?).read_exact(&mut flushed)?;
+        assert_eq!(&flushed, b"a\nb\nc\n");
+
+        let finished = sink.finalize()?;
+        assert_eq!(finished[0].lines, 3);
+        assert_eq!(finished[0].bytes, std::fs::metadata(&path)?.len());
+
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(File::open(&path)?).read_to_string(&mut text)?;
+        assert_eq!(text, "a\nb\nc\n");
+
+        // Appending would strand the new data under the .partial name
+        let append = FileOptions { existing: Existing::Append, ..files };
+        let err = GzipSink::create(&path, 0, Format::Plain, &append, 1024).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert!(!Path::new(&partial_path(&path)).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_part_filename() {
+        assert_eq!(part_filename("output.txt", 1), "output.0001.txt");
+        assert_eq!(part_filename("out/results-2.txt", 12), "out/results-2.0012.txt");
+    }
+
+    #[test]
+    fn test_rotation() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("out.txt").to_string_lossy().into_owned();
+        let output = ShardOutput {
+            path,
+            routing: Routing::RoundRobin,
+            rotation: Rotation { max_bytes: Some(10), max_lines: Some(3) },
+            existing: Existing::Overwrite,
+            buffer_size: 1024,
+            checksums: true,
+        };
+        let mut writer = PartWriter::create(0, output).map_err(|(_, e)| e)?;
+
+        let mut files = Vec::new();
+        for line in ["a", "b", "c", "d", "eeeeeeee", "f"] {
+            files.extend(writer.write_line(format!("{}\n", line).as_bytes())?);
+        }
+        // The open part keeps its temporary name until finished
+        let last = dir.path().join("out.0004.txt");
+        assert!(!last.exists());
+        assert!(dir.path().join("out.0004.txt.partial").exists());
+        files.push(writer.finish()?);
+        assert!(last.exists());
+        assert!(!dir.path().join("out.0004.txt.partial").exists());
+
+        // Rotated on the line limit, then twice on the byte limit
+        let lines: Vec<u64> = files.iter().map(|f| f.lines).collect();
+        assert_eq!(lines, vec![3, 1, 1, 1]);
+        assert!(files[2].path.ends_with("out.0003.txt"));
+        assert_eq!(std::fs::read_to_string(&files[2].path)?, "eeeeeeee\n");
+        // sha256("a\nb\nc\n")
+        assert_eq!(
+            files[0].sha256.as_deref(),
+            Some("880553fca8fcea94e325ee2cfb48e5a985cc797f39a14cc6d3cedecfeb2ae4d2")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_append_and_refuse() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("out.txt");
+        std::fs::write(&path, "a\nb")?;
+        let output = |existing| ShardOutput {
+            path: path.to_string_lossy().into_owned(),
+            routing: Routing::RoundRobin,
+            rotation: Rotation::default(),
+            existing,
+            buffer_size: 1024,
+            checksums: true,
+        };
+
+        assert!(PartWriter::create(0, output(Existing::Refuse)).is_err());
+
+        let mut writer = PartWriter::create(0, output(Existing::Append)).map_err(|(_, e)| e)?;
+        writer.write_line(b"c\n")?;
+        let file = writer.finish()?;
+        assert_eq!(std::fs::read_to_string(&path)?, "a\nb\nc\n");
+        // Totals and checksum cover the whole file, sha256("a\nb\nc\n")
+        assert_eq!((file.lines, file.bytes), (3, 6));
+        assert_eq!(
+            file.sha256.as_deref(),
+            Some("880553fca8fcea94e325ee2cfb48e5a985cc797f39a14cc6d3cedecfeb2ae4d2")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_append_continues_rotation() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let output = || ShardOutput {
+            path: dir.path().join("out.txt").to_string_lossy().into_owned(),
+            routing: Routing::RoundRobin,
+            rotation: Rotation { max_bytes: None, max_lines: Some(2) },
+            existing: Existing::Append,
+            buffer_size: 1024,
+            checksums: false,
+        };
+        let read = |name: &str| std::fs::read_to_string(dir.path().join(name));
+
+        // Two runs with the same rotation settings
+        for lines in [["a", "b", "c"], ["d", "e", "f"]] {
+            let mut writer = PartWriter::create(0, output()).map_err(|(_, e)| e)?;
+            for line in lines {
+                writer.write_line(format!("{}\n", line).as_bytes())?;
+            }
+            writer.finish()?;
+        }
+
+        // The second run fills the part the first left open before starting a new one
+        assert_eq!(read("out.0001.txt")?, "a\nb\n");
+        assert_eq!(read("out.0002.txt")?, "c\nd\n");
+        assert_eq!(read("out.0003.txt")?, "e\nf\n");
+        assert!(!dir.path().join("out.0004.txt").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_base_files_pool() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let output = ShardOutput {
+            path: dir.path().to_string_lossy().into_owned(),
+            routing: Routing::PerBase { max_open: 2 },
+            rotation: Rotation::default(),
+            existing: Existing::Overwrite,
+            buffer_size: 1024,
+            checksums: true,
+        };
+        let mut files = BaseFiles::new(0, output, 2);
+        let (a, b, c): (Arc<str>, Arc<str>, Arc<str>) = ("a.com".into(), "b.com".into(), "c.com".into());
+
+        files.write_line(&a, b"x.a.com\n")?;
+        files.write_line(&b, b"x.b.com\n")?;
+        // Opening c closes a, which is reopened for appending
+        files.write_line(&c, b"x.c.com\n")?;
+        assert!(files.files[&a].writer.is_none());
+        files.write_line(&a, b"y.a.com\n")?;
+        assert!(files.open.len() <= 2);
+        // c was opened before a but written after it, so reopening b closes a
+        files.write_line(&c, b"y.c.com\n")?;
+        files.write_line(&b, b"y.b.com\n")?;
+        assert!(files.files[&a].writer.is_none());
+        assert!(files.files[&c].writer.is_some());
+
+        let finished = files.finish()?;
+        let lines: Vec<u64> = finished.iter().map(|f| f.lines).collect();
+        assert_eq!(lines, vec![2, 2, 2]);
+        assert!(finished[0].path.ends_with("a.com.txt"));
+        assert_eq!(std::fs::read_to_string(&finished[0].path)?, "x.a.com\ny.a.com\n");
+        Ok(())
+    }
+}
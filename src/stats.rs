@@ -0,0 +1,305 @@
+use crate::dns;
+use crate::rr::ShardMonitor;
+use anyhow::{Context, Result};
+use colored::*;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Counters updated on the generation hot path
+pub struct Counters {
+    /// Names produced by the generator before any filter, shared with the progress display
+    pub generated: Arc<AtomicU64>,
+    /// Names handed to the writers
+    pub emitted: AtomicU64,
+    /// Names over the DNS length limit
+    pub too_long: AtomicU64,
+    /// Names found in the exclude file
+    pub excluded: AtomicU64,
+    level_generated: Vec<AtomicU64>,
+    level_emitted: Vec<AtomicU64>,
+}
+
+impl Counters {
+    pub fn new(max_level: u32) -> Self {
+        let per_level = || (0..max_level).map(|_| AtomicU64::new(0)).collect();
+        Self {
+            generated: Arc::new(AtomicU64::new(0)),
+            emitted: AtomicU64::new(0),
+            too_long: AtomicU64::new(0),
+            excluded: AtomicU64::new(0),
+            level_generated: per_level(),
+            level_emitted: per_level(),
+        }
+    }
+
+    pub fn record_generated(&self, level: u32) {
+        self.generated.fetch_add(1, Ordering::Relaxed);
+        self.level_generated[level as usize - 1].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_emitted(&self, level: u32) {
+        self.emitted.fetch_add(1, Ordering::Relaxed);
+        self.level_emitted[level as usize - 1].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn generated(&self) -> u64 {
+        self.generated.load(Ordering::Relaxed)
+    }
+
+    pub fn emitted(&self) -> u64 {
+        self.emitted.load(Ordering::Relaxed)
+    }
+
+    /// Per-level totals for depths `min..=max`
+    pub fn levels(&self, min: u32) -> Vec<LevelReport> {
+        (min..=self.level_generated.len() as u32)
+            .map(|level| LevelReport {
+                level,
+                generated: self.level_generated[level as usize - 1].load(Ordering::Relaxed),
+                emitted: self.level_emitted[level as usize - 1].load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+/// Summary of a generation run, written by `--stats-json`
+#[derive(Serialize)]
+pub struct RunStats {
+    pub version: &'static str,
+    pub wall_time_secs: f64,
+    /// Stopped early by Ctrl+C
+    pub interrupted: bool,
+    /// Stopped early by a run-wide line, byte or time cap
+    pub limit_reached: bool,
+    /// Bases cut short by a per-base cap
+    pub truncated_bases: u64,
+    pub names_per_sec: f64,
+    pub bytes_per_sec: f64,
+    pub bases: BaseSummary,
+    pub words: WordSummary,
+    pub names: NameSummary,
+    pub levels: Vec<LevelReport>,
+    pub per_base: Vec<BaseReport>,
+    pub shards: Vec<ShardReport>,
+}
+
+#[derive(Serialize)]
+pub struct BaseSummary {
+    /// Bases generation ran for
+    pub processed: usize,
+    pub skipped: usize,
+    pub duplicates: usize,
+    pub public_suffixes: usize,
+}
+
+#[derive(Serialize)]
+pub struct WordSummary {
+    /// Non-empty wordlist lines
+    pub lines: usize,
+    /// Unique words used for generation
+    pub loaded: usize,
+    pub rejected: usize,
+    pub sanitized: usize,
+    /// Left out by --regex
+    pub filtered: usize,
+    pub duplicates: usize,
+    pub weighted: bool,
+    /// Heap memory held by the loaded words and weights
+    pub memory_bytes: usize,
+    /// Estimated memory saved over holding each word as its own `String` plus a `HashSet`
+    /// copy for deduplication
+    pub memory_saved_bytes: usize,
+}
+
+#[derive(Serialize)]
+pub struct NameSummary {
+    pub generated: u64,
+    pub emitted: u64,
+    pub too_long: u64,
+    /// Dropped by --match/--filter; null when neither is given
+    pub filtered: Option<u64>,
+    /// Dropped by --exclude-file; null without one
+    pub excluded: Option<u64>,
+    /// Dropped by --dedupe; null when off
+    pub duplicates: Option<u64>,
+    pub scope: Vec<ScopeRuleReport>,
+    /// Names matching no include rule, when the scope file has include rules
+    pub scope_unmatched: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct ScopeRuleReport {
+    pub rule: String,
+    pub suppressed: u64,
+}
+
+#[derive(Serialize)]
+pub struct LevelReport {
+    pub level: u32,
+    pub generated: u64,
+    pub emitted: u64,
+}
+
+#[derive(Serialize)]
+pub struct BaseReport {
+    pub base: String,
+    pub generated: u64,
+    pub emitted: u64,
+}
+
+#[derive(Serialize)]
+pub struct ShardReport {
+    pub shard: usize,
+    /// Output file, or none when writing to stdout only
+    pub path: Option<String>,
+    pub lines: u64,
+    pub bytes: u64,
+    /// Stopped on a write error
+    pub failed: bool,
+}
+
+impl ShardReport {
+    pub fn new(shard: usize, monitor: &ShardMonitor) -> Self {
+        Self {
+            shard,
+            path: monitor.path.clone(),
+            lines: monitor.stats.lines.load(Ordering::Relaxed),
+            bytes: monitor.stats.bytes.load(Ordering::Relaxed),
+            failed: monitor.failed(),
+        }
+    }
+}
+
+impl RunStats {
+    /// Print the end-of-run memory, suppression and limit figures to stderr
+    pub fn print_summary(&self, scoped: bool) {
+        let mib = |bytes: usize| bytes as f64 / (1024.0 * 1024.0);
+        eprintln!(
+            "{} Wordlist held in {:.1} MiB (~{:.1} MiB saved over per-word strings, estimated)",
+            "📦".bright_blue(),
+            mib(self.words.memory_bytes),
+            mib(self.words.memory_saved_bytes)
+        );
+
+        let names = &self.names;
+        if names.too_long > 0 {
+            eprintln!(
+                "{} {} names longer than {} bytes skipped",
+                "⚠️".yellow(),
+                names.too_long.to_string().yellow().bold(),
+                dns::MAX_NAME_LEN
+            );
+        }
+
+        if let Some(filtered) = names.filtered {
+            eprintln!(
+                "{} {} names dropped by --match/--filter",
+                "🔍".bright_blue(),
+                filtered.to_string().bright_cyan().bold()
+            );
+        }
+
+        if let Some(duplicates) = names.duplicates {
+            eprintln!(
+                "{} {} duplicate names dropped",
+                "🧮".bright_blue(),
+                duplicates.to_string().bright_cyan().bold()
+            );
+        }
+
+        if let Some(excluded) = names.excluded {
+            eprintln!(
+                "{} {} already-known names excluded",
+                "📚".bright_blue(),
+                excluded.to_string().bright_cyan().bold()
+            );
+        }
+
+        if scoped {
+            eprintln!("{} {}", "🎯".bright_blue(), "Scope suppressions:".bright_white().bold());
+            for rule in &names.scope {
+                eprintln!("   {} {}", rule.suppressed.to_string().bright_cyan().bold(), rule.rule);
+            }
+            if let Some(unmatched) = names.scope_unmatched {
+                eprintln!("   {} {}", unmatched.to_string().bright_cyan().bold(), "(no include rule matched)".bright_black());
+            }
+        }
+
+        if self.limit_reached {
+            eprintln!(
+                "{} {}",
+                "⛔".yellow(),
+                "Output limit reached, generation stopped early".yellow().bold()
+            );
+        }
+        if self.truncated_bases > 0 {
+            eprintln!(
+                "{} {} bases cut short by per-base limits",
+                "⛔".yellow(),
+                self.truncated_bases.to_string().yellow().bold()
+            );
+        }
+    }
+
+    pub fn write(&self, path: &str) -> Result<()> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create stats file: {}", path))?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, self).context("Failed to serialize run stats")?;
+        writeln!(writer)?;
+        writer.flush().with_context(|| format!("Failed to write stats file: {}", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_counters() {
+        let counters = Counters::new(3);
+        counters.record_generated(1);
+        counters.record_generated(3);
+        counters.record_generated(3);
+        counters.record_emitted(3);
+
+        assert_eq!(counters.generated(), 3);
+        assert_eq!(counters.emitted(), 1);
+
+        let levels = counters.levels(2);
+        assert_eq!(levels.len(), 2);
+        assert_eq!((levels[0].level, levels[0].generated), (2, 0));
+        assert_eq!((levels[1].level, levels[1].generated, levels[1].emitted), (3, 2, 1));
+    }
+
+    #[test]
+    fn test_json_layout() -> Result<()> {
+        let counters = Counters::new(1);
+        counters.record_generated(1);
+        let json = serde_json::to_value(counters.levels(1))?;
+        assert_eq!(json, serde_json::json!([{ "level": 1, "generated": 1, "emitted": 0 }]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unused_filters_are_null() -> Result<()> {
+        let names = NameSummary {
+            generated: 2,
+            emitted: 2,
+            too_long: 0,
+            filtered: Some(0),
+            excluded: None,
+            duplicates: None,
+            scope: Vec::new(),
+            scope_unmatched: None,
+        };
+        let json = serde_json::to_value(names)?;
+        assert_eq!(json["filtered"], 0);
+        assert!(json["excluded"].is_null());
+        assert!(json["duplicates"].is_null());
+        Ok(())
+    }
+}